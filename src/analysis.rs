@@ -0,0 +1,117 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::field::Field;
+use crate::misc::MiscMethods;
+use crate::solver::{index_to_coords, simulate};
+use crate::tile::*;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// Поля больше этого числа клеток разбираются без симуляции игрока: она перерешивает всё поле
+/// на каждом ходу и на огромных полях занимает минуты.
+pub const SIMULATION_TILE_LIMIT : usize = 1024;
+
+/// Сколько шагов симуляции разрешено разбору. Разбор идёт прямо в первом клике, а шаг на поле 30x30
+/// стоит около миллисекунды; если шагов не хватило, оценка угадываний остаётся неизвестной.
+pub const SIMULATION_STEPS : usize = 256;
+
+/// Метка сложности поля по его анализу.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Difficulty {
+    Easy, // проходится одними выводами и измерениями
+    Hard  // хотя бы в одном исходе измерений приходится угадывать
+}
+
+impl Difficulty {
+    pub fn to_u8(&self) -> u8 {
+        return *self as u8;
+    }
+}
+
+/// Разбор сгенерированного поля (см. `Analyzer::analyze`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoardAnalysis {
+    pub clicks : u32,        // квантовый 3BV: оценка сверху числа кликов, чтобы пройти поле
+    pub openings : u32,      // области нулей, которые открываются одним кликом
+    pub superposition : f64, // доля мин в группах, где кандидатов больше, чем мин
+    pub guesses : Option<f64> // сколько ходов наугад нужно игроку; `None` для полей больше `SIMULATION_TILE_LIMIT` и без исхода за `SIMULATION_STEPS` шагов
+}
+
+impl BoardAnalysis {
+    /// Сложность по угадываниям; `None`, если симуляция не запускалась.
+    pub fn difficulty(&self) -> Option<Difficulty> {
+        return self.guesses.map(|guesses| if guesses > 0.0 { Difficulty::Hard } else { Difficulty::Easy });
+    }
+}
+
+/// Классический 3BV: сколько кликов нужно, чтобы открыть все безопасные клетки, если мины известны,
+/// и сколько из них приходится на области нулей. Опасной считается любая клетка с ненулевой вероятностью.
+pub fn board_complexity(field : &Field) -> (u32, u32) {
+    let safe = |i : usize| field.tiles[i].prob == Prob::ZERO;
+    let zero = |i : usize| {
+        let (x, y) = index_to_coords(field, i);
+        return safe(i) && field.around_prob_sum(x, y).is_ok_and(|sum| sum == Prob::ZERO);
+    };
+    let mut visited = vec![false; field.tiles.len()];
+    let mut openings = 0;
+
+    for start in 0..field.tiles.len() {
+        if visited[start] || !zero(start) { continue; }
+        openings += 1;
+        visited[start] = true;
+        let mut queue = VecDeque::from([start]);
+        while let Some(i) = queue.pop_front() {
+            let (x, y) = index_to_coords(field, i);
+            for (nx, ny) in field.neighbours(x, y) {
+                let Some(n) = field.coords_to_index(nx, ny) else { continue; };
+                if visited[n] || !safe(n) { continue; }
+                visited[n] = true;
+                if zero(n) { queue.push_back(n); }
+            }
+        }
+    }
+    let singles = (0..field.tiles.len()).filter(|&i| !visited[i] && safe(i)).count() as u32;
+    return (openings + singles, openings);
+}
+
+pub trait Analyzer {
+    /// Разбор поля сразу после генерации. Квантовый 3BV - классический 3BV, где кандидаты считаются
+    /// минами, плюс по каждой неизмеренной группе клик измерения и клики по клеткам, которые
+    /// освободятся после коллапса. Это оценка сверху: каскады после коллапса часто открывают
+    /// освободившиеся клетки без отдельных кликов. Число угадываний оценивается симуляцией игрока
+    /// (см. `simulate`) на исходе измерений из `seed`, если поле не больше `SIMULATION_TILE_LIMIT`.
+    fn analyze(&self, first_x : i32, first_y : i32, quant_flags : usize, seed : u64) -> BoardAnalysis;
+}
+
+impl Analyzer for Field {
+    fn analyze(&self, first_x : i32, first_y : i32, quant_flags : usize, seed : u64) -> BoardAnalysis {
+        let (classic, openings) = board_complexity(self);
+
+        let mut groups : BTreeMap<GroupId, (usize, Prob)> = BTreeMap::new();
+        for tile in self.tiles.iter().filter(|t| !t.collapsed) {
+            let Some(group) = tile.mine_id else { continue; };
+            groups.entry(group).or_insert((0, tile.prob.clone())).0 += 1;
+        }
+        let measure_clicks : usize = groups.values()
+            .map(|(size, prob)| 1 + size - prob.0 as usize * size / Prob::DENOMINATOR as usize)
+            .sum();
+
+        let total : u64 = self.tiles.iter().map(|t| t.prob.0 as u64).sum();
+        let uncertain : u64 = self.tiles.iter().filter(|t| t.prob != Prob::ZERO && t.prob != Prob::MINE).map(|t| t.prob.0 as u64).sum();
+        let superposition = if total > 0 { uncertain as f64 / total as f64 } else { 0.0 };
+
+        let guesses = (self.tiles.len() <= SIMULATION_TILE_LIMIT)
+            .then(|| simulate(self, first_x, first_y, quant_flags, seed, true, SIMULATION_STEPS))
+            .flatten()
+            .map(|run| run.guesses as f64);
+
+        return BoardAnalysis {
+            clicks: classic + measure_clicks as u32,
+            openings,
+            superposition,
+            guesses
+        };
+    }
+}
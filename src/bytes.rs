@@ -1,49 +1,545 @@
-// СЕЙЧАС ЭТОТ КОД НЕ ИСПОЛЬЗУЕТСЯ
+use fastrand::Rng;
 
-/*
-use rand::thread_rng;
+use crate::analysis::BoardAnalysis;
+use crate::changes::{all_changes, Journal};
+use crate::engine::{GameConfig, GameEngine, GameState, ToolType};
+use crate::error::EngineError;
+use crate::field::{check_groups, Entanglement, Field, Tiling, Topology};
+use crate::replay::Replay;
+use crate::summary::GameStats;
+use crate::tile::{GroupId, Tile};
 
-use crate::field::Field;
-use crate::tile::Tile;
+// Формат сохранения (все числа little-endian):
+// сигнатура (`Byter::MAGIC`) | версия (u8) | данные (см. `write_bytes` у конкретного типа)
+pub const FORMAT_VERSION : u8 = 1;
 
-pub trait Byter {
-    fn to_bytes(&self) -> Vec<u8>;
-    fn from_bytes(bytes : &[u8]) -> Result<Field, &'static str>;
+fn corrupted(reason : String) -> EngineError {
+    return EngineError::CorruptedSave(reason);
 }
 
+/// Последовательно читает сохранение, возвращая ошибку вместо паники при нехватке данных.
+pub struct ByteReader<'a> {
+    bytes : &'a [u8],
+    offset : usize
+}
 
-impl Byter for Field {
+impl<'a> ByteReader<'a> {
+    pub fn new(bytes : &'a [u8]) -> ByteReader<'a> {
+        return ByteReader { bytes, offset: 0 };
+    }
+
+    pub fn remaining(&self) -> usize {
+        return self.bytes.len() - self.offset;
+    }
+
+    pub fn take(&mut self, len : usize) -> Result<&'a [u8], EngineError> {
+        if self.remaining() < len {
+            return Err(corrupted(String::from("data is truncated")));
+        }
+        let slice = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        return Ok(slice);
+    }
+
+    pub fn u8(&mut self) -> Result<u8, EngineError> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    pub fn bool(&mut self) -> Result<bool, EngineError> {
+        match self.u8()? {
+            0 => return Ok(false),
+            1 => return Ok(true),
+            value => return Err(corrupted(format!("invalid boolean ({})", value)))
+        }
+    }
+
+    /// Номер группы; `u32::MAX` в связях не бывает.
+    pub fn group_id(&mut self) -> Result<GroupId, EngineError> {
+        let id = self.u32()?;
+        return GroupId::new(id as usize).ok_or(corrupted(format!("invalid group id ({})", id)));
+    }
+
+    pub fn i32(&mut self) -> Result<i32, EngineError> {
+        return Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()));
+    }
+
+    pub fn u32(&mut self) -> Result<u32, EngineError> {
+        return Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()));
+    }
+
+    pub fn u64(&mut self) -> Result<u64, EngineError> {
+        return Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()));
+    }
+
+    pub fn f64(&mut self) -> Result<f64, EngineError> {
+        return Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()));
+    }
+
+    pub fn finish(&self) -> Result<(), EngineError> {
+        if self.remaining() > 0 {
+            return Err(corrupted(format!("{} unexpected trailing bytes", self.remaining())));
+        }
+        return Ok(());
+    }
+}
+
+pub trait Byter : Sized {
+    const MAGIC : &'static [u8; 4] = b"QSWP";
+
+    fn write_bytes(&self, out : &mut Vec<u8>);
+    fn read_bytes(reader : &mut ByteReader) -> Result<Self, EngineError>;
+
+    /// Сериализует значение вместе с заголовком формата.
     fn to_bytes(&self) -> Vec<u8> {
-        let mut result : Vec<u8> = Vec::new();
-        result.extend(self.width.to_ne_bytes());
-        result.extend(self.height.to_ne_bytes());
+        let mut result = Vec::new();
+        result.extend_from_slice(Self::MAGIC);
+        result.push(FORMAT_VERSION);
+        self.write_bytes(&mut result);
+        return result;
+    }
+
+    /// Проверяет заголовок, читает значение и убеждается, что лишних данных нет.
+    fn from_bytes(bytes : &[u8]) -> Result<Self, EngineError> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.take(Self::MAGIC.len())? != Self::MAGIC {
+            return Err(corrupted(String::from("it isn't a quantsweeper save")));
+        }
+        let version = reader.u8()?;
+        if version != FORMAT_VERSION {
+            return Err(EngineError::UnsupportedVersion { found: version, expected: FORMAT_VERSION });
+        }
+        let result = Self::read_bytes(&mut reader)?;
+        reader.finish()?;
+        return Ok(result);
+    }
+}
+
+impl Byter for Field {
+    // ширина (u32) | высота (u32) | топология (u8) | форма клеток (u8) | размеры групп (u8, u8) | мин в группе (u8)
+    // | доля запутанных групп (f64) | число связей (u32) | связи (u32, u32, u8) | были ли пробы щупом (u8)
+    // | состояние генератора (u64) | клетки по `Tile::BYTES` | зафиксированы ли исходы (u8)
+    // | исходы, по биту на клетку (только если зафиксированы)
+    fn write_bytes(&self, out : &mut Vec<u8>) {
+        out.extend(self.width.to_le_bytes());
+        out.extend(self.height.to_le_bytes());
+        out.push(self.topology.to_u8());
+        out.push(self.tiling.to_u8());
+        out.push(*self.group_sizes.start() as u8);
+        out.push(*self.group_sizes.end() as u8);
+        out.push(self.group_mines as u8);
+        out.extend(self.entangled.to_le_bytes());
+        out.extend((self.entanglement.len() as u32).to_le_bytes());
+        for (a, b, kind) in &self.entanglement {
+            out.extend(a.0.to_le_bytes());
+            out.extend(b.0.to_le_bytes());
+            out.push(kind.to_u8());
+        }
+        out.push(self.probed as u8);
+        out.extend(self.rng.get_seed().to_le_bytes());
         for tile in &self.tiles {
-            result.extend_from_slice(&tile.to_bytes());
+            out.extend_from_slice(&tile.to_bytes());
         }
-        return result;
+        out.push(!self.outcomes.is_empty() as u8);
+        for chunk in self.outcomes.chunks(8) {
+            out.push(chunk.iter().enumerate().fold(0u8, |byte, (bit, &mine)| byte | ((mine as u8) << bit)));
+        }
+    }
+
+    fn read_bytes(reader : &mut ByteReader) -> Result<Field, EngineError> {
+        let width = reader.u32()?;
+        let height = reader.u32()?;
+        let topology = Topology::from_u8(reader.u8()?)?;
+        let tiling = Tiling::from_u8(reader.u8()?)?;
+        let group_sizes = reader.u8()? as usize..=reader.u8()? as usize;
+        let group_mines = reader.u8()? as usize;
+        check_groups(&group_sizes, group_mines).map_err(|e| corrupted(e.to_string()))?;
+        let entangled = reader.f64()?;
+        let links = reader.u32()? as usize;
+        if reader.remaining() < links.saturating_mul(9) {
+            return Err(corrupted(String::from("data is truncated")));
+        }
+        let mut entanglement = Vec::with_capacity(links);
+        for _ in 0..links {
+            entanglement.push((reader.group_id()?, reader.group_id()?, Entanglement::from_u8(reader.u8()?)?));
+        }
+        let probed = reader.bool()?;
+        let rng = Rng::with_seed(reader.u64()?);
+        let total = (width as usize).checked_mul(height as usize)
+            .ok_or(corrupted(format!("field size {}x{} overflows", width, height)))?;
+        // проверяем длину до выделения памяти, чтобы испорченный размер не заказал гигабайты
+        if reader.remaining() < total.saturating_mul(Tile::BYTES) {
+            return Err(corrupted(String::from("data is truncated")));
+        }
+        let mut tiles = Vec::with_capacity(total);
+        for _ in 0..total {
+            tiles.push(Tile::from_bytes(reader.take(Tile::BYTES)?)?);
+        }
+        // номер группы не больше числа клеток, иначе индекс групп заказал бы память по испорченному номеру
+        let mut present = vec![false; total];
+        for tile in &tiles {
+            let Some(group) = tile.mine_id else { continue; };
+            *present.get_mut(group.index()).ok_or(corrupted(format!("group {} is out of range", group)))? = true;
+        }
+        // связь с группой без клеток сломала бы коллапс партнёра посреди игры
+        for &(a, b, _) in &entanglement {
+            for group in [a, b] {
+                if !present.get(group.index()).copied().unwrap_or(false) {
+                    return Err(corrupted(format!("entangled group {} has no tiles", group)));
+                }
+            }
+        }
+        let outcomes = if reader.bool()? {
+            let bytes = reader.take(total.div_ceil(8))?;
+            (0..total).map(|i| bytes[i / 8] >> (i % 8) & 1 == 1).collect()
+        } else {
+            Vec::new()
+        };
+        let mut field = Field { width, height, tiles, topology, tiling, group_sizes, group_mines, entangled, entanglement, group_tiles: Vec::new(), probed, outcomes, journal: Journal::default(), rng };
+        field.index_groups();
+        return Ok(field);
+    }
+}
+
+impl Byter for GameConfig {
+    // ширина (u32) | высота (u32) | группы (f64) | кандидаты (f64) | есть ли сид (u8) | сид (u64) | отмена (u8)
+    // | без угадывания (u8) | попытки генерации (u32) | цена подсказки (u32) | топология (u8)
+    // | форма клеток (u8) | мин. размер группы (u32) | макс. размер группы (u32) | мин в группе (u32)
+    // | доля запутанных групп (f64) | щупы (u32) | огромное поле (u8)
+    fn write_bytes(&self, out : &mut Vec<u8>) {
+        out.extend(self.width.to_le_bytes());
+        out.extend(self.height.to_le_bytes());
+        out.extend(self.groups.to_le_bytes());
+        out.extend(self.candidates.to_le_bytes());
+        out.push(self.seed.is_some() as u8);
+        out.extend(self.seed.unwrap_or(0).to_le_bytes());
+        out.push(self.undo as u8);
+        out.push(self.no_guess as u8);
+        out.extend(self.generation_attempts.to_le_bytes());
+        out.extend(self.hint_cost.to_le_bytes());
+        out.push(self.topology.to_u8());
+        out.push(self.tiling.to_u8());
+        out.extend(self.min_group_size.to_le_bytes());
+        out.extend(self.max_group_size.to_le_bytes());
+        out.extend(self.group_mines.to_le_bytes());
+        out.extend(self.entangled.to_le_bytes());
+        out.extend(self.probes.to_le_bytes());
+        out.push(self.huge as u8);
+    }
+
+    fn read_bytes(reader : &mut ByteReader) -> Result<GameConfig, EngineError> {
+        let width = reader.u32()?;
+        let height = reader.u32()?;
+        let groups = reader.f64()?;
+        let candidates = reader.f64()?;
+        let has_seed = reader.bool()?;
+        let seed = reader.u64()?;
+        let undo = reader.bool()?;
+        let no_guess = reader.bool()?;
+        let generation_attempts = reader.u32()?;
+        let hint_cost = reader.u32()?;
+        let topology = Topology::from_u8(reader.u8()?)?;
+        let tiling = Tiling::from_u8(reader.u8()?)?;
+        let min_group_size = reader.u32()?;
+        let max_group_size = reader.u32()?;
+        let group_mines = reader.u32()?;
+        let entangled = reader.f64()?;
+        let probes = reader.u32()?;
+        let huge = reader.bool()?;
+        for value in [groups, candidates, entangled] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(corrupted(format!("invalid config percent ({})", value)));
+            }
+        }
+        return Ok(GameConfig { width, height, groups, candidates, seed: has_seed.then_some(seed), undo, no_guess, generation_attempts, hint_cost, topology, tiling, min_group_size, max_group_size, group_mines, entangled, probes, huge });
+    }
+}
+
+impl Byter for GameStats {
+    // клики, открытия, флажки, квантовые флажки, коллапсы, подсказки (u32) | есть ли начало (u8) | начало (f64)
+    // | есть ли конец (u8) | конец (f64) | есть ли пауза (u8) | начало паузы (f64)
+    fn write_bytes(&self, out : &mut Vec<u8>) {
+        for counter in [self.clicks, self.opens, self.flags, self.quant_flags, self.collapses, self.hints] {
+            out.extend(counter.to_le_bytes());
+        }
+        for time in [self.started_at, self.finished_at, self.paused_at] {
+            out.push(time.is_some() as u8);
+            out.extend(time.unwrap_or(0.0).to_le_bytes());
+        }
+    }
+
+    fn read_bytes(reader : &mut ByteReader) -> Result<GameStats, EngineError> {
+        let mut stats = GameStats {
+            clicks: reader.u32()?,
+            opens: reader.u32()?,
+            flags: reader.u32()?,
+            quant_flags: reader.u32()?,
+            collapses: reader.u32()?,
+            hints: reader.u32()?,
+            ..GameStats::default()
+        };
+        for time in [&mut stats.started_at, &mut stats.finished_at, &mut stats.paused_at] {
+            let is_set = reader.bool()?;
+            let value = reader.f64()?;
+            *time = is_set.then_some(value);
+        }
+        return Ok(stats);
+    }
+}
+
+impl Byter for BoardAnalysis {
+    // клики (u32) | области нулей (u32) | доля суперпозиции (f64) | есть ли оценка угадываний (u8) | угадывания (f64)
+    fn write_bytes(&self, out : &mut Vec<u8>) {
+        out.extend(self.clicks.to_le_bytes());
+        out.extend(self.openings.to_le_bytes());
+        out.extend(self.superposition.to_le_bytes());
+        out.push(self.guesses.is_some() as u8);
+        out.extend(self.guesses.unwrap_or(0.0).to_le_bytes());
     }
 
-    fn from_bytes(bytes : &[u8]) -> Result<Field, &'static str> {
-        if bytes.len() >= 8 {
-            let width = u32::from_ne_bytes(bytes[0..4].try_into().unwrap());
-            let height = u32::from_ne_bytes(bytes[4..8].try_into().unwrap());
-            println!("{}x{}", width, height);
-            let mut tiles : Vec<Tile> = Vec::new();
-            let total = width.checked_mul(height).ok_or("Mul size overflow")?;
-            if bytes.len() < 8 + total as usize * 3 {
-                return Err("Data lose some tiles");
+    fn read_bytes(reader : &mut ByteReader) -> Result<BoardAnalysis, EngineError> {
+        let (clicks, openings, superposition) = (reader.u32()?, reader.u32()?, reader.f64()?);
+        let has_guesses = reader.bool()?;
+        let guesses = reader.f64()?;
+        return Ok(BoardAnalysis { clicks, openings, superposition, guesses: has_guesses.then_some(guesses) });
+    }
+}
+
+impl Byter for GameState {
+    // стадия (u8) | для проигрыша клетка взрыва (i32, i32)
+    fn write_bytes(&self, out : &mut Vec<u8>) {
+        match self {
+            GameState::NotStarted => out.push(0),
+            GameState::Playing => out.push(1),
+            GameState::Won => out.push(2),
+            GameState::Lost { x, y } => {
+                out.push(3);
+                out.extend(x.to_le_bytes());
+                out.extend(y.to_le_bytes());
+            },
+            GameState::Paused => out.push(4)
+        }
+    }
+
+    fn read_bytes(reader : &mut ByteReader) -> Result<GameState, EngineError> {
+        match reader.u8()? {
+            0 => return Ok(GameState::NotStarted),
+            1 => return Ok(GameState::Playing),
+            2 => return Ok(GameState::Won),
+            3 => return Ok(GameState::Lost { x: reader.i32()?, y: reader.i32()? }),
+            4 => return Ok(GameState::Paused),
+            value => return Err(corrupted(format!("unknown game state ({})", value)))
+        }
+    }
+}
+
+impl Byter for GameEngine {
+    // [стадия партии] | инструмент | квантовые флажки (u32) | щупы (u32) | сид (u64) | [статистика]
+    // | есть ли разбор (u8) | [разбор поля] | [конфиг] | [поле] | [запись партии]
+    fn write_bytes(&self, out : &mut Vec<u8>) {
+        self.state.write_bytes(out);
+        out.push(self.current_tool.to_u8());
+        out.extend((self.flag_count as u32).to_le_bytes());
+        out.extend((self.probe_count as u32).to_le_bytes());
+        out.extend(self.seed.to_le_bytes());
+        self.stats.write_bytes(out);
+        out.push(self.analysis.is_some() as u8);
+        if let Some(analysis) = &self.analysis {
+            analysis.write_bytes(out);
+        }
+        out.push(self.config.is_some() as u8);
+        if let Some(config) = &self.config {
+            config.write_bytes(out);
+        }
+        out.push(self.current_field.is_some() as u8);
+        if let Some(field) = &self.current_field {
+            field.write_bytes(out);
+        }
+        out.push(self.replay.is_some() as u8);
+        if let Some(replay) = &self.replay {
+            replay.write_bytes(out);
+        }
+    }
+
+    fn read_bytes(reader : &mut ByteReader) -> Result<GameEngine, EngineError> {
+        let mut engine = GameEngine::new();
+        engine.state = GameState::read_bytes(reader)?;
+        engine.current_tool = ToolType::from_u8(reader.u8()?)?;
+        engine.flag_count = reader.u32()? as usize;
+        engine.probe_count = reader.u32()? as usize;
+        engine.seed = reader.u64()?;
+        engine.stats = GameStats::read_bytes(reader)?;
+        if reader.bool()? {
+            engine.analysis = Some(BoardAnalysis::read_bytes(reader)?);
+        }
+        if reader.bool()? {
+            engine.config = Some(GameConfig::read_bytes(reader)?);
+        }
+        if reader.bool()? {
+            let field = Field::read_bytes(reader)?;
+            let config = engine.config.as_ref()
+                .ok_or(corrupted(String::from("field is saved without config")))?;
+            if (field.width, field.height) != (config.width, config.height) {
+                return Err(corrupted(format!("field {}x{} doesn't match config {}x{}",
+                    field.width, field.height, config.width, config.height)));
             }
-            for i in 0..total {
-                let start = 8u32.checked_add(i.checked_mul(3).ok_or("Mul overflow")?).ok_or("Offset overflow")? as usize;
-                let end = start.checked_add(3).ok_or("End offset overflow")? as usize;
-                let range = start..end;
-
-                let slice = &bytes[range];
-                let tile = Tile::from_bytes(slice);
-                tiles.push(tile?);
+            if (field.topology, field.tiling, &field.group_sizes, field.group_mines, field.entangled)
+                != (config.topology, config.tiling, &config.group_sizes(), config.group_mines as usize, config.entangled) {
+                return Err(corrupted(String::from("field shape doesn't match config")));
             }
-            return Ok(Field {width, height, tiles, rng: thread_rng()});
+            engine.field_changes = all_changes(&field);
+            engine.current_field = Some(field);
         }
-        return Err("Data length less than 8 (size isn't defined)");
+        if reader.bool()? {
+            engine.replay = Some(Replay::read_bytes(reader)?);
+        }
+        return Ok(engine);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::Generator;
+    use crate::replay::Move;
+    use crate::tile::TileStatus;
+
+    fn generated_field() -> Field {
+        let mut field = Field::new(10, 10, 7);
+        field.generate(5, 5, 0.1, 0.2).unwrap();
+        return field;
     }
-}*/
\ No newline at end of file
+
+    fn is_corrupted<T>(result : Result<T, EngineError>) -> bool {
+        return matches!(result, Err(EngineError::CorruptedSave(_)));
+    }
+
+    // партия с измерениями, щупом, флажками и отменой, чтобы в сохранение попали все части движка
+    fn played_engine() -> GameEngine {
+        let config = GameConfig::builder(12, 10).groups(0.12).candidates(0.3).seed(11).probes(2).entangled(0.5).build().unwrap();
+        let mut engine = GameEngine::new();
+        engine.start_new_game(config).unwrap();
+        engine.tile_interact(6, 5).unwrap();
+        let closed : Vec<(i32, i32)> = (0..120).map(|i| (i % 12, i / 12))
+            .filter(|&(x, y)| engine.get_tile_status(x, y) == Ok(TileStatus::None)).collect();
+        let tools = [ToolType::QuantFlag, ToolType::SimpleFlag, ToolType::Probe, ToolType::QuantFlag];
+        for (&(x, y), tool) in closed.iter().zip(tools) {
+            engine.change_tool(tool);
+            let _ = engine.tile_interact(x, y);
+        }
+        let _ = engine.collapse_quant_flags();
+        let _ = engine.undo();
+        return engine;
+    }
+
+    #[test]
+    fn engine_round_trip() {
+        let engine = played_engine();
+        let bytes = engine.save_game();
+        let loaded = GameEngine::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.save_game(), bytes);
+        assert_eq!(loaded.field().unwrap().tiles, engine.field().unwrap().tiles);
+        assert_eq!(loaded.state(), engine.state());
+        assert_eq!(loaded.quant_flags(), engine.quant_flags());
+        assert_eq!(loaded.probes(), engine.probes());
+        assert_eq!(loaded.replay().unwrap().moves, engine.replay().unwrap().moves);
+        assert!(loaded.replay().unwrap().moves.contains(&Move::Undo));
+    }
+
+    #[test]
+    fn undo_of_first_click_round_trip() {
+        let config = GameConfig::builder(10, 10).seed(1).entangled(1.0).build().unwrap();
+        let mut engine = GameEngine::new();
+        engine.start_new_game(config).unwrap();
+        engine.tile_interact(5, 5).unwrap();
+        let played = engine.save_game();
+        assert!(engine.undo().unwrap());
+        // отмена первого клика возвращает пустое поле без групп и разбора
+        let field = engine.field().unwrap();
+        assert!(field.entanglement.is_empty() && field.group_tiles.is_empty() && field.outcomes.is_empty());
+        assert!(engine.analysis().is_none());
+        let bytes = engine.save_game();
+        assert_eq!(GameEngine::from_bytes(&bytes).unwrap().save_game(), bytes);
+
+        assert!(engine.redo().unwrap());
+        let loaded = GameEngine::from_bytes(&engine.save_game()).unwrap();
+        assert_eq!(loaded.field().unwrap().to_bytes(), GameEngine::from_bytes(&played).unwrap().field().unwrap().to_bytes());
+        assert_eq!(loaded.analysis(), GameEngine::from_bytes(&played).unwrap().analysis());
+    }
+
+    #[test]
+    fn parts_round_trip() {
+        let engine = played_engine();
+        let config = engine.config().unwrap();
+        assert_eq!(&GameConfig::from_bytes(&config.to_bytes()).unwrap(), config);
+        let field = engine.field().unwrap();
+        assert_eq!(Field::from_bytes(&field.to_bytes()).unwrap().to_bytes(), field.to_bytes());
+        let analysis = engine.analysis().unwrap();
+        assert_eq!(&BoardAnalysis::from_bytes(&analysis.to_bytes()).unwrap(), analysis);
+        for state in [GameState::NotStarted, GameState::Playing, GameState::Won, GameState::Lost { x: 3, y: -1 }, GameState::Paused] {
+            assert_eq!(GameState::from_bytes(&state.to_bytes()).unwrap(), state);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_save() {
+        let bytes = played_engine().save_game();
+        for len in 0..bytes.len() {
+            assert!(is_corrupted(GameEngine::from_bytes(&bytes[..len])), "prefix of {} bytes is accepted", len);
+        }
+    }
+
+    #[test]
+    fn rejects_corrupted_header_and_tail() {
+        let bytes = played_engine().save_game();
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] ^= 0xFF;
+        assert!(is_corrupted(GameEngine::from_bytes(&wrong_magic)));
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[GameEngine::MAGIC.len()] = FORMAT_VERSION + 1;
+        assert_eq!(GameEngine::from_bytes(&wrong_version).err(),
+            Some(EngineError::UnsupportedVersion { found: FORMAT_VERSION + 1, expected: FORMAT_VERSION }));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(is_corrupted(GameEngine::from_bytes(&trailing)));
+    }
+
+    #[test]
+    fn corrupted_bytes_never_panic() {
+        let bytes = played_engine().save_game();
+        let mut rng = Rng::with_seed(5);
+        for _ in 0..2000 {
+            let mut broken = bytes.clone();
+            for _ in 0..rng.usize(1..4) {
+                let i = rng.usize(GameEngine::MAGIC.len() + 1..broken.len());
+                broken[i] = rng.u8(..);
+            }
+            // испорченное сохранение либо читается, либо отклоняется ошибкой, но не роняет движок
+            let _ = GameEngine::from_bytes(&broken);
+        }
+    }
+
+    #[test]
+    fn rejects_group_id_out_of_range() {
+        let field = generated_field();
+        let mut bytes = field.to_bytes();
+        // номер группы первой клетки, сразу за её статусом; клетки идут перед байтом исходов
+        let offset = bytes.len() - 1 - field.tiles.len() * Tile::BYTES + 1;
+        bytes[offset..offset + 4].copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+        assert!(is_corrupted(Field::from_bytes(&bytes)));
+    }
+
+    #[test]
+    fn rejects_link_to_missing_group() {
+        let mut field = generated_field();
+        let missing = GroupId(field.tiles.len() as u32 - 1);
+        assert!(field.tiles.iter().all(|tile| tile.mine_id != Some(missing)));
+        field.entanglement.push((GroupId(0), missing, Entanglement::Mirror));
+        assert!(is_corrupted(Field::from_bytes(&field.to_bytes())));
+
+        field.entanglement.pop();
+        field.entanglement.push((GroupId(0), GroupId(u32::MAX - 1), Entanglement::Mirror));
+        assert!(is_corrupted(Field::from_bytes(&field.to_bytes())));
+    }
+}
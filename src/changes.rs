@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+
+use crate::field::Field;
+use crate::misc::MiscMethods;
+use crate::solver::index_to_coords;
+use crate::tile::*;
+
+/// Изменение одной клетки за последнее действие. `flags` - набор причин (`TileChange::STATUS` и т.д.).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileChange {
+    pub x : i32,
+    pub y : i32,
+    pub flags : u8
+}
+
+impl TileChange {
+    pub const STATUS : u8 = 1;    // открыта, поставлен или снят флажок
+    pub const SUM : u8 = 2;       // изменилась сумма вероятностей вокруг открытой клетки
+    pub const COLLAPSED : u8 = 4; // клетка измерена или измерение отменено
+    pub const MINE : u8 = 8;      // под клеткой оказалась мина или мина ушла при отмене
+    pub const MARKED : u8 = 16;   // флажок на мине подсвечен или погашен вместе с концом игры
+
+    pub fn has(&self, flag : u8) -> bool {
+        return self.flags & flag != 0;
+    }
+}
+
+/// Журнал действия: индексы тронутых клеток с их видом до изменения (см. `Field::tile_mut`).
+/// Копия поля журнал не наследует, чтобы симуляции на копиях не писали в журнал игры.
+#[derive(Debug, Default)]
+pub struct Journal(pub Option<Vec<(usize, Tile)>>);
+
+impl Clone for Journal {
+    fn clone(&self) -> Journal {
+        return Journal(None);
+    }
+}
+
+/// Все клетки поля как изменённые: после новой игры и загрузки сохранения интерфейс рисует поле заново.
+pub fn all_changes(field : &Field) -> Vec<TileChange> {
+    return (0..field.tiles.len()).map(|i| {
+        let (x, y) = index_to_coords(field, i);
+        return TileChange { x, y, flags: TileChange::STATUS };
+    }).collect();
+}
+
+/// Сравнивает тронутые действием клетки (`Field::take_journal`) с полем после него. Каждая клетка поля
+/// попадает в список не больше одного раза, в порядке индексов; `game_over_changed` - игра закончилась
+/// или отмена её вернула.
+pub fn diff_changes(touched : &[(usize, Tile)], field : &Field, game_over_changed : bool) -> Vec<TileChange> {
+    let mut flags : BTreeMap<usize, u8> = BTreeMap::new();
+    let mine = |tile : &Tile| tile.prob == Prob::MINE;
+    // вероятность клетки до действия: журнал отсортирован по индексу
+    let old_prob = |n : usize| match touched.binary_search_by_key(&n, |(i, _)| *i) {
+        Ok(pos) => touched[pos].1.prob.0 as u64,
+        Err(_) => field.tiles[n].prob.0 as u64
+    };
+    let sum = |x : i32, y : i32, prob : &dyn Fn(usize) -> u64| -> u64 {
+        return field.neighbours(x, y).into_iter()
+            .filter_map(|(nx, ny)| field.coords_to_index(nx, ny))
+            .map(prob).sum();
+    };
+
+    for (i, old) in touched {
+        let (i, new) = (*i, &field.tiles[*i]);
+        if old == new { continue; }
+        let mut change = 0;
+        if old.status != new.status { change |= TileChange::STATUS; }
+        if old.collapsed != new.collapsed { change |= TileChange::COLLAPSED; }
+        if mine(old) != mine(new) { change |= TileChange::MINE; }
+        if change != 0 {
+            *flags.entry(i).or_default() |= change;
+        }
+        if old.prob == new.prob { continue; }
+        // у соседей может поменяться число, но видно его только на открытых клетках
+        let (x, y) = index_to_coords(field, i);
+        for (nx, ny) in field.neighbours(x, y) {
+            let Some(n) = field.coords_to_index(nx, ny) else { continue; };
+            if field.tiles[n].status == TileStatus::Opened && sum(nx, ny, &old_prob) != sum(nx, ny, &|n| field.tiles[n].prob.0 as u64) {
+                *flags.entry(n).or_default() |= TileChange::SUM;
+            }
+        }
+    }
+    if game_over_changed {
+        for (i, tile) in field.tiles.iter().enumerate() {
+            if tile.status == TileStatus::Flag && mine(tile) {
+                *flags.entry(i).or_default() |= TileChange::MARKED;
+            }
+        }
+    }
+    return flags.into_iter().map(|(i, flags)| {
+        let (x, y) = index_to_coords(field, i);
+        return TileChange { x, y, flags };
+    }).collect();
+}
@@ -1,119 +1,149 @@
-use std::collections::HashSet;
-use fastrand;
+use std::collections::BTreeSet;
 
-use crate::field::{Field, DIRECTIONS};
+use crate::error::EngineError;
+use crate::field::{Entanglement, Field};
 use crate::misc::MiscMethods;
+use crate::solver::index_to_coords;
 use crate::tile::*;
 
 pub trait Collapser {
-    fn collapse_simple_tile(&mut self, x: i32, y: i32) -> Result<(), String>;
-    fn collapse_group(&mut self, group_id : i16) -> Result<(), String>;
-    fn collapse(&mut self, x: i32, y: i32) -> Result<(), String>;
-    fn get_tiles_with_quant_flags(&self) -> HashSet<i16>;
-    fn collapse_quant_flag_groups(&mut self, quantum_groups : &HashSet<i16>) -> Result<Vec<(i32, i32)>, Vec<String>>;
-    fn collapse_quant_flags(&mut self) -> Result<Vec<(i32, i32)>, String> ;
+    fn collapse_simple_tile(&mut self, x: i32, y: i32) -> Result<(), EngineError>;
+    fn collapse_group(&mut self, group_id : GroupId) -> Result<(), EngineError>;
+    fn collapse(&mut self, x: i32, y: i32) -> Result<(), EngineError>;
+    /// Группы клеток с квантовыми флажками; `None` - флажки на клетках вне групп.
+    fn get_tiles_with_quant_flags(&self) -> BTreeSet<Option<GroupId>>;
+    fn collapse_quant_flag_groups(&mut self, quantum_groups : &BTreeSet<Option<GroupId>>) -> Result<Vec<(i32, i32)>, EngineError>;
+    fn collapse_quant_flags(&mut self) -> Result<Vec<(i32, i32)>, EngineError>;
+    fn probe(&mut self, x: i32, y: i32) -> Result<bool, EngineError>;
+}
+
+// коллапсирует одну группу и возвращает позиции мин в ней; без `forced` мины выбираются случайно
+// (разных клеток частичной перетасовкой Фишера-Йетса), иначе по исходу запутанного партнёра.
+// Зафиксированный исход (`Field::outcomes`) уже согласован с партнёрами и берётся как есть
+fn place_mines(field : &mut Field, target_mine : GroupId, forced : Option<(Entanglement, &[usize])>) -> Result<Vec<usize>, EngineError> {
+    let indices = field.group_indices(target_mine);
+
+    if indices.is_empty() {
+        return Err(EngineError::GroupNotFound(target_mine));
+    }
+    if field.tiles[indices[0]].collapsed {
+        return Err(EngineError::GroupCollapsed(target_mine));
+    }
+    // после щупа в группе может остаться меньше мин, поэтому число берётся из вероятности
+    let mines = (field.tiles[indices[0]].prob.0 as usize * indices.len() / Prob::DENOMINATOR as usize).min(indices.len());
+    for &i in &indices {
+        let tile = field.tile_mut(i);
+        tile.collapsed = true;
+        tile.prob = Prob::ZERO;
+    }
+    let positions = match forced {
+        _ if !field.outcomes.is_empty() => (0..indices.len()).filter(|&p| field.outcomes[indices[p]]).collect(),
+        Some((kind, partner_positions)) => {
+            let mut positions = kind.map(partner_positions, indices.len());
+            positions.truncate(mines);
+            let mut free = (0..indices.len()).filter(|p| !positions.contains(p)).collect::<Vec<usize>>().into_iter();
+            while positions.len() < mines {
+                positions.extend(free.next());
+            }
+            positions
+        },
+        None => {
+            let mut order : Vec<usize> = (0..indices.len()).collect();
+            for i in 0..mines {
+                let mine_index = field.rng.usize(i..order.len());
+                order.swap(i, mine_index);
+            }
+            order.truncate(mines);
+            order
+        }
+    };
+    for &position in &positions {
+        field.tile_mut(indices[position]).prob = Prob::MINE;
+    }
+    return Ok(positions);
 }
 
 impl Collapser for Field {
-    fn collapse_simple_tile(&mut self, x: i32, y: i32) -> Result<(), String> {
-        let tile = self.get_mut_tile(x, y).ok_or(format!("Unfound tile at ({}, {})", x, y))?;
+    fn collapse_simple_tile(&mut self, x: i32, y: i32) -> Result<(), EngineError> {
+        let tile = self.get_mut_tile(x, y).ok_or(EngineError::OutOfBounds { x, y })?;
         
         if !tile.collapsed && tile.status != TileStatus::Opened {
             tile.collapsed = true;
-            tile.prob = Prob(0);
+            tile.prob = Prob::ZERO;
+        }
+        else if tile.collapsed {
+            return Err(EngineError::AlreadyCollapsed { x, y });
         }
         else {
-            return Err(format!("Tile ({}, {}) has been already opened or collapsed;", x, y));
+            return Err(EngineError::AlreadyOpened { x, y });
         }
         return Ok(());
     }
 
-    fn collapse_group(&mut self, target_mine : i16) -> Result<(), String> {
-        let mut matching_indices: Vec<&mut Tile> = self.tiles
-            .iter_mut().filter(|t| t.mine_id == target_mine).collect();
-
-        if matching_indices.is_empty() {
-            return Err(format!("Cant found tiles with that group ({})", target_mine));
-        }
-        if matching_indices[0].collapsed {
-            return Err(format!("Tiles that group ({}) have been already collapsed.", target_mine));
-        }
-        for tile in &mut matching_indices {
-            tile.collapsed = true;
-            tile.prob = Prob(0);
-        }
-        let mine_index = fastrand::usize(0..matching_indices.len());
-        if let Some(mine_tile) = matching_indices.get_mut(mine_index) {
-            mine_tile.prob = Prob(12);
-        }
-        else {
-            return Err(format!("Unfound mine candidate in group ({})", target_mine));
+    fn collapse_group(&mut self, target_mine : GroupId) -> Result<(), EngineError> {
+        let positions = place_mines(self, target_mine, None)?;
+        // исход измерения передаётся по графу запутанности: партнёр коллапсирует вместе с группой
+        let mut queue = vec![(target_mine, positions)];
+        while let Some((group, positions)) = queue.pop() {
+            for (partner, kind) in self.partners(group) {
+                match place_mines(self, partner, Some((kind, &positions))) {
+                    Ok(partner_positions) => queue.push((partner, partner_positions)),
+                    Err(EngineError::GroupCollapsed(_)) => {},
+                    Err(e) => return Err(e)
+                }
+            }
         }
         return Ok(());
     }
 
-    fn collapse(&mut self, x: i32, y: i32) -> Result<(), String> {
-        let tile = self.get_mut_tile(x, y).ok_or(format!("Tile at ({}, {}) isn't found", x, y))?;
+    fn collapse(&mut self, x: i32, y: i32) -> Result<(), EngineError> {
+        let tile = self.get_tile(x, y).ok_or(EngineError::OutOfBounds { x, y })?;
         if tile.collapsed {
-            return Err(format!("Tile ({}, {}) has been already collapsed.", x, y));
+            return Err(EngineError::AlreadyCollapsed { x, y });
         }
 
-        let mine_id = tile.mine_id;
-
-        if mine_id == -1 {
-            self.collapse_simple_tile(x, y)?;
-            return Ok(());
-        } else {
-            self.collapse_group(mine_id)?;
-            return Ok(());
+        match tile.mine_id {
+            None => self.collapse_simple_tile(x, y)?,
+            Some(mine_id) => self.collapse_group(mine_id)?
         }
+        return Ok(());
     }
 
-    fn get_tiles_with_quant_flags(&self) -> HashSet<i16> {
-        let mut quantum_groups = HashSet::new();
-        
-        for y in 0..self.height {
-            for x in 0..self.width {
-                if let Some(tile) = self.get_tile(x as i32, y as i32) {
-                    if tile.status == TileStatus::QuantFlag {
-                        quantum_groups.insert(tile.mine_id);
-                    }
-                }
-            }
-        }
-        return quantum_groups;
+    fn get_tiles_with_quant_flags(&self) -> BTreeSet<Option<GroupId>> {
+        // упорядоченный набор, иначе порядок коллапсов (и генератора) плавает
+        return self.tiles.iter().filter(|tile| tile.status == TileStatus::QuantFlag).map(|tile| tile.mine_id).collect();
     }
 
-    fn collapse_quant_flag_groups(&mut self, quantum_groups : &HashSet<i16>) -> Result<Vec<(i32, i32)>, Vec<String>> {
-        let mut error_bank : Vec<String> = Vec::new();
+    // возвращает клетки, с которых сняты флажки; числа вокруг них и вокруг запутанных
+    // партнёров тоже меняются, но движок находит это по журналу клеток (см. `changes::diff_changes`)
+    fn collapse_quant_flag_groups(&mut self, quantum_groups : &BTreeSet<Option<GroupId>>) -> Result<Vec<(i32, i32)>, EngineError> {
         let mut modificied : Vec<(i32, i32)> = Vec::new();
         for &group_id in quantum_groups {
-            if group_id == -1 {
-                for y in 0..self.height as i32 {
-                    for x in 0..self.width as i32 {
+            match group_id {
+                None => {
+                    for i in 0..self.tiles.len() {
+                        let tile = &self.tiles[i];
+                        if tile.status != TileStatus::QuantFlag || tile.mine_id.is_some() { continue; }
+                        let tile = self.tile_mut(i);
+                        tile.collapsed = true;
+                        tile.status = TileStatus::None;
+                        modificied.push(index_to_coords(self, i));
+                    }
+                },
+                Some(group_id) => {
+                    // флажок на уже измеренной группе просто снимается
+                    match self.collapse_group(group_id) {
+                        Ok(()) | Err(EngineError::GroupCollapsed(_)) => {},
+                        Err(e) => return Err(e)
+                    }
+
+                    for (x, y) in self.get_group_elements(group_id) {
                         if let Some(tile) = self.get_mut_tile(x, y) {
-                            if tile.status == TileStatus::QuantFlag && tile.mine_id == -1 {
-                                tile.collapsed = true;
+                            if tile.status != TileStatus::None {
                                 tile.status = TileStatus::None;
+                                modificied.push((x, y));
                             }
                         }
-                        modificied.push((x, y));
-                        for (dx, dy) in DIRECTIONS {
-                            modificied.push((x + dx, y + dy));
-                        }
-                    }
-                }
-            } else {
-                let _ = self.collapse_group(group_id).unwrap_or_else(|e| error_bank.push(e));
-                
-                let group_coords = self.get_group_elements(group_id);
-                for (x, y) in group_coords {
-                    if let Some(tile) = self.get_mut_tile(x, y) {
-                        tile.status = TileStatus::None;
-                    }
-                    modificied.push((x, y));
-                    for (dx, dy) in DIRECTIONS {
-                        modificied.push((x + dx, y + dy));
                     }
                 }
             }
@@ -121,10 +151,102 @@ impl Collapser for Field {
         return Ok(modificied);
     }
 
-    fn collapse_quant_flags(&mut self) -> Result<Vec<(i32, i32)>, String> {
+    fn collapse_quant_flags(&mut self) -> Result<Vec<(i32, i32)>, EngineError> {
         let quantum_groups = self.get_tiles_with_quant_flags();
 
-        return Ok(self.collapse_quant_flag_groups(&quantum_groups)
-            .map_err(|error_bank| error_bank.join("\n"))?);
+        return self.collapse_quant_flag_groups(&quantum_groups);
+    }
+
+    /// Слабое измерение: есть ли мина под клеткой. Клетка выходит из группы и коллапсирует,
+    /// остальные клетки группы получают вероятность `оставшиеся мины / оставшиеся клетки`
+    /// (например, 1/4 -> 1/3 -> 1/2 -> 1). Возвращает `true`, если под клеткой мина.
+    fn probe(&mut self, x: i32, y: i32) -> Result<bool, EngineError> {
+        let index = self.coords_to_index(x, y).ok_or(EngineError::OutOfBounds { x, y })?;
+        let tile = &self.tiles[index];
+        if tile.status == TileStatus::Opened {
+            return Err(EngineError::AlreadyOpened { x, y });
+        }
+        if tile.collapsed {
+            return Err(EngineError::AlreadyCollapsed { x, y });
+        }
+        let Some(group) = tile.mine_id else {
+            self.collapse_simple_tile(x, y)?;
+            return Ok(false);
+        };
+
+        let others : Vec<usize> = self.group_indices(group).into_iter().filter(|&i| i != index).collect();
+        let size = others.len() + 1;
+        let mines = self.tiles[index].prob.0 as usize * size / Prob::DENOMINATOR as usize;
+        let is_mine = match self.outcomes.get(index) {
+            Some(&outcome) => outcome,
+            None => self.rng.usize(0..size) < mines
+        };
+        let tile = self.tile_mut(index);
+        tile.collapsed = true;
+        // последняя клетка остаётся в группе: так связи запутанности не указывают на пустую группу
+        if !others.is_empty() {
+            tile.mine_id = None;
+        }
+        tile.prob = if is_mine { Prob::MINE } else { Prob::ZERO };
+
+        // доли мин меняются, только если в группе остались другие клетки
+        self.probed |= !others.is_empty();
+        let rest = (mines - is_mine as usize) as u32;
+        for i in others.iter().copied() {
+            self.tile_mut(i).prob = Prob(Prob::DENOMINATOR * rest / others.len() as u32);
+        }
+        return Ok(is_mine);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{GameConfig, GameEngine, ToolType};
+    use crate::generator::Generator;
+
+    #[test]
+    fn probe_renormalises_group() {
+        for seed in 0..20 {
+            let mut field = Field::new(10, 10, seed);
+            field.group_sizes = 4..=4;
+            field.group_mines = 2;
+            field.generate(0, 0, 0.1, 0.4).unwrap();
+            let group = field.group_indices(GroupId(0));
+            assert_eq!(group.len(), 4);
+            // 2/4 -> доли оставшихся мин среди оставшихся клеток после каждого щупа
+            let mut mines = 2;
+            for (probed, &index) in group.iter().enumerate() {
+                let (x, y) = index_to_coords(&field, index);
+                mines -= field.probe(x, y).unwrap() as u32;
+                let rest = &group[probed + 1..];
+                for &other in rest {
+                    assert_eq!(field.tiles[other].prob, Prob(Prob::DENOMINATOR * mines / rest.len() as u32));
+                }
+                assert!(field.probed);
+            }
+            assert_eq!(mines, 0);
+            assert_eq!(group.iter().filter(|&&i| field.tiles[i].prob == Prob::MINE).count(), 2);
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn undo_restores_probed() {
+        let config = GameConfig::builder(10, 10).seed(4).probes(3).build().unwrap();
+        let mut engine = GameEngine::new();
+        engine.start_new_game(config).unwrap();
+        engine.tile_interact(5, 5).unwrap();
+        let field = engine.field().unwrap();
+        let index = (0..field.tiles.len()).find(|&i| {
+            let tile = &field.tiles[i];
+            return tile.status == TileStatus::None && tile.mine_id.is_some_and(|group| field.group_indices(group).len() > 1);
+        }).unwrap();
+        let (x, y) = index_to_coords(field, index);
+        engine.change_tool(ToolType::Probe);
+        engine.tile_interact(x, y).unwrap();
+        assert!(engine.field().unwrap().probed);
+        assert!(engine.undo().unwrap());
+        assert!(!engine.field().unwrap().probed);
+        assert!(engine.redo().unwrap());
+        assert!(engine.field().unwrap().probed);
+    }
+}
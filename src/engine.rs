@@ -1,193 +1,837 @@
-use js_sys::{Array, Object, Reflect};
+use crate::analysis::*;
+use crate::bytes::Byter;
+use crate::changes::*;
+use crate::collapser::Collapser;
+use crate::error::EngineError;
+use crate::field::{check_groups, Field, Partner, TileShape, Tiling, Topology};
+use crate::generator::{group_counts, Generator, DEFAULT_GROUP_SIZES};
+use crate::hint::*;
+use crate::history::*;
+use crate::misc::MiscMethods;
+use crate::odds::Odds;
+use crate::opener::TileOpener;
+use crate::replay::*;
+use crate::solver::*;
+use crate::summary::*;
+use crate::tile::*;
+
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-use crate::{
-    collapser::Collapser, field::Field, generator::Generator, misc::MiscMethods, opener::TileOpener, tile::*
-};
-
-// ERRORS
-const UNDEFINED_FIELD : &'static str =  "Field isn't defined";
-const UNDEFINED_CONFIG : &'static str = "Config isn't defined";
-
-#[wasm_bindgen]
-#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ToolType {
     Shovel,
     SimpleFlag,
-    QuantFlag
+    QuantFlag,
+    Probe // слабое измерение одной клетки (см. `Collapser::probe`)
+}
+
+impl ToolType {
+    pub fn to_u8(&self) -> u8 {
+        return *self as u8;
+    }
+
+    pub fn from_u8(value : u8) -> Result<ToolType, EngineError> {
+        match value {
+            0 => return Ok(ToolType::Shovel),
+            1 => return Ok(ToolType::SimpleFlag),
+            2 => return Ok(ToolType::QuantFlag),
+            3 => return Ok(ToolType::Probe),
+            _ => return Err(EngineError::CorruptedSave(format!("unknown tool ({})", value)))
+        }
+    }
+}
+
+/// Стадия партии.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameState {
+    NotStarted,                // поле ещё не сгенерировано, ждём первого клика
+    Playing,
+    Won,
+    Lost { x : i32, y : i32 }, // клетка, на которой взорвалась мина
+    Paused                     // таймер остановлен, ходы запрещены
+}
+
+impl GameState {
+    pub fn is_over(&self) -> bool {
+        return matches!(self, GameState::Won | GameState::Lost { .. });
+    }
 }
 
-struct GameConfig {
-    width : u32,
-    height : u32,
-    groups : f64,
-    candidates : f64
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameConfig {
+    pub width : u32,
+    pub height : u32,
+    pub groups : f64,
+    pub candidates : f64,
+    pub seed : Option<u64>, // None - случайный сид
+    pub undo : bool, // false - отмена ходов запрещена (рейтинговые игры)
+    pub no_guess : bool, // поле проходится без угадывания
+    pub generation_attempts : u32, // сколько полей перебрать в режиме без угадывания
+    pub hint_cost : u32, // сколько квантовых флажков стоит подсказка (0 - бесплатно)
+    pub topology : Topology, // склейка краёв поля
+    pub tiling : Tiling, // форма клеток
+    pub min_group_size : u32, // сколько клеток минимум в квантовой группе
+    pub max_group_size : u32, // и максимум (не больше `MAX_GROUP_SIZE`)
+    pub group_mines : u32, // сколько мин в каждой группе (не больше минимального размера)
+    pub entangled : f64, // доля групп в запутанных парах (0 - без запутанности)
+    pub probes : u32, // сколько слабых измерений (щупов) даётся на игру
+    pub huge : bool // огромное поле: стороны до `MAX_HUGE_SIDE`, но без режима без угадывания
 }
 
-const NON_FLAGGED : Tile = Tile::new();
+impl GameConfig {
+    pub fn new(width : u32, height : u32, groups : f64, candidates : f64) -> GameConfig {
+        return GameConfig {
+            width, height, groups, candidates,
+            seed: None,
+            undo: true,
+            no_guess: false,
+            generation_attempts: DEFAULT_GENERATION_ATTEMPTS,
+            hint_cost: 0,
+            topology: Topology::Rectangle,
+            tiling: Tiling::Square,
+            min_group_size: *DEFAULT_GROUP_SIZES.start() as u32,
+            max_group_size: *DEFAULT_GROUP_SIZES.end() as u32,
+            group_mines: 1,
+            entangled: 0.0,
+            probes: 0,
+            huge: false
+        };
+    }
+
+    /// Сборка конфига с проверкой в `GameConfigBuilder::build`.
+    pub fn builder(width : u32, height : u32) -> GameConfigBuilder {
+        return GameConfigBuilder { config: GameConfig::new(width, height, DEFAULT_GROUPS, DEFAULT_CANDIDATES) };
+    }
+
+    pub fn group_sizes(&self) -> std::ops::RangeInclusive<usize> {
+        return self.min_group_size as usize..=self.max_group_size as usize;
+    }
+
+    /// Проверяет конфиг по правилам игры, одинаковым для всех клиентов:
+    /// - стороны поля от `MIN_SIDE` до `MAX_SIDE` (с `huge` - до `MAX_HUGE_SIDE`),
+    ///   склейка подходит форме клеток (`Tiling::check_size`);
+    /// - размеры групп и число мин в группе допустимы (`check_groups`);
+    /// - доля групп в (0, 1], доля кандидатов не больше 1 и не больше `groups * max_group_size`,
+    ///   а кандидатов хватает, чтобы каждая группа получила `min_group_size` клеток;
+    /// - доля запутанных групп от 0 до 1, щупов не больше `MAX_PROBES`,
+    ///   режим без угадывания не на огромном поле и хотя бы с одной попыткой генерации.
+    pub fn validate(&self) -> Result<(), EngineError> {
+        let invalid = |message : String| Err(EngineError::InvalidConfig(message));
+        let max_side = if self.huge { MAX_HUGE_SIDE } else { MAX_SIDE };
+        for (name, side) in [("width", self.width), ("height", self.height)] {
+            if !(MIN_SIDE..=max_side).contains(&side) {
+                return invalid(format!("{} must be within {}..={}, got {}", name, MIN_SIDE, max_side, side));
+            }
+        }
+        self.tiling.check_size(self.width, self.height, self.topology)?;
+        check_groups(&self.group_sizes(), self.group_mines as usize)?;
+        if !(self.groups > 0.0 && self.groups <= 1.0) {
+            return invalid(format!("groups share must be within (0, 1], got {}", self.groups));
+        }
+        // доли приходят из процентов интерфейса, поэтому сравниваются с небольшим допуском
+        if !(0.0..=1.0).contains(&self.candidates) || self.candidates > self.groups * self.max_group_size as f64 + 1e-9 {
+            return invalid(format!("candidates share must be within 0..={}, got {}",
+                (self.groups * self.max_group_size as f64).min(1.0), self.candidates));
+        }
+        let (groups, candidates) = group_counts(self.width as usize * self.height as usize, self.groups, self.candidates);
+        if candidates < groups * self.min_group_size as usize {
+            return invalid(format!("candidates ({}) fewer than groups ({}) of {} tiles", candidates, groups, self.min_group_size));
+        }
+        if !(0.0..=1.0).contains(&self.entangled) {
+            return invalid(format!("entangled share must be within 0..=1, got {}", self.entangled));
+        }
+        if self.probes > MAX_PROBES {
+            return invalid(format!("probes must be within 0..={}, got {}", MAX_PROBES, self.probes));
+        }
+        if self.no_guess && self.huge {
+            return invalid(String::from("no-guess mode isn't available on huge boards"));
+        }
+        if self.no_guess && self.generation_attempts == 0 {
+            return invalid(String::from("no-guess mode needs at least one generation attempt"));
+        }
+        return Ok(());
+    }
+
+    /// Сколько групп, кандидатов и квантовых флажков получит игра с этим конфигом, до первого клика.
+    pub fn counts(&self) -> Result<ConfigCounts, EngineError> {
+        self.validate()?;
+        let (groups, candidates) = group_counts(self.width as usize * self.height as usize, self.groups, self.candidates);
+        return Ok(ConfigCounts {
+            groups,
+            candidates: candidates.min(groups * self.max_group_size as usize), // лишние кандидаты генератор не раздаёт
+            quant_flags: self.quant_flag_count()
+        });
+    }
+
+    pub(crate) fn quant_flag_count(&self) -> usize {
+        return (self.width as f64 * self.height as f64 * self.groups * self.group_mines as f64 * 1.3) as usize;
+    }
+}
+
+/// Числа, которые следуют из конфига (см. `GameConfig::counts`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfigCounts {
+    pub groups : usize,
+    pub candidates : usize,
+    pub quant_flags : usize
+}
+
+/// Пошаговая сборка `GameConfig`: `GameConfig::builder(10, 10).groups(0.1).candidates(0.2).build()`.
+/// Без вызовов - значения `GameConfig::new` с долями `DEFAULT_GROUPS` и `DEFAULT_CANDIDATES`.
+#[derive(Debug, Clone)]
+pub struct GameConfigBuilder {
+    config : GameConfig
+}
+
+impl GameConfigBuilder {
+    pub fn groups(mut self, groups : f64) -> GameConfigBuilder {
+        self.config.groups = groups;
+        return self;
+    }
+
+    pub fn candidates(mut self, candidates : f64) -> GameConfigBuilder {
+        self.config.candidates = candidates;
+        return self;
+    }
+
+    pub fn seed(mut self, seed : u64) -> GameConfigBuilder {
+        self.config.seed = Some(seed);
+        return self;
+    }
+
+    pub fn undo(mut self, undo : bool) -> GameConfigBuilder {
+        self.config.undo = undo;
+        return self;
+    }
+
+    pub fn no_guess(mut self, no_guess : bool) -> GameConfigBuilder {
+        self.config.no_guess = no_guess;
+        return self;
+    }
+
+    pub fn generation_attempts(mut self, attempts : u32) -> GameConfigBuilder {
+        self.config.generation_attempts = attempts;
+        return self;
+    }
+
+    pub fn hint_cost(mut self, cost : u32) -> GameConfigBuilder {
+        self.config.hint_cost = cost;
+        return self;
+    }
+
+    pub fn topology(mut self, topology : Topology) -> GameConfigBuilder {
+        self.config.topology = topology;
+        return self;
+    }
+
+    pub fn tiling(mut self, tiling : Tiling) -> GameConfigBuilder {
+        self.config.tiling = tiling;
+        return self;
+    }
+
+    pub fn group_sizes(mut self, min : u32, max : u32) -> GameConfigBuilder {
+        self.config.min_group_size = min;
+        self.config.max_group_size = max;
+        return self;
+    }
+
+    pub fn group_mines(mut self, mines : u32) -> GameConfigBuilder {
+        self.config.group_mines = mines;
+        return self;
+    }
+
+    pub fn entangled(mut self, entangled : f64) -> GameConfigBuilder {
+        self.config.entangled = entangled;
+        return self;
+    }
+
+    pub fn probes(mut self, probes : u32) -> GameConfigBuilder {
+        self.config.probes = probes;
+        return self;
+    }
+
+    pub fn huge(mut self, huge : bool) -> GameConfigBuilder {
+        self.config.huge = huge;
+        return self;
+    }
 
-#[wasm_bindgen]
-struct GameEngine {
-    current_field : Option<Field>,
-    flag_count : usize,
-    first_click : bool,
-    config : Option<GameConfig>,
-    is_game_over : bool,
-    current_tool : ToolType,
+    /// Готовый конфиг или ошибка `InvalidConfig` (см. `GameConfig::validate`).
+    pub fn build(self) -> Result<GameConfig, EngineError> {
+        self.config.validate()?;
+        return Ok(self.config);
+    }
+}
 
-    field_changes : Vec<(i32, i32)> // оптимизация
+pub const DEFAULT_GENERATION_ATTEMPTS : u32 = 50;
+pub const DEFAULT_GROUPS : f64 = 0.1;     // доля групп в `GameConfig::builder`
+pub const DEFAULT_CANDIDATES : f64 = 0.2; // доля кандидатов там же
+
+pub const MIN_SIDE : u32 = 5;
+pub const MAX_SIDE : u32 = 30;
+pub const MAX_HUGE_SIDE : u32 = 2048; // огромные поля (`GameConfig::huge`), до 4 млн клеток
+pub const MAX_PROBES : u32 = 20;
+
+pub const BOARD_VIEW_STRIDE : usize = 3;   // вид клетки, числитель, знаменатель
+pub const CHANGES_VIEW_STRIDE : usize = 6; // x, y, причины изменения и то же, что в `board_view`
+
+/// Игровая сессия без привязки к JS: поле, конфиг, инструменты и список изменённых клеток.
+#[derive(Clone)]
+pub struct GameEngine {
+    pub(crate) current_field : Option<Field>,
+    pub(crate) flag_count : usize,
+    pub(crate) probe_count : usize,
+    pub(crate) config : Option<GameConfig>,
+    pub(crate) seed : u64,
+    pub(crate) state : GameState,
+    pub(crate) current_tool : ToolType,
+
+    pub(crate) history : History,
+    pub(crate) replay : Option<Replay>,
+    pub(crate) stats : GameStats,
+    pub(crate) analysis : Option<BoardAnalysis>, // разбор поля после генерации
+
+    pub(crate) field_changes : Vec<TileChange> // что перерисовать после последнего действия
+}
+
+impl Default for GameEngine {
+    fn default() -> Self {
+        return GameEngine::new();
+    }
 }
 
-#[wasm_bindgen]
-#[allow(dead_code)]
 impl GameEngine {
-    #[wasm_bindgen(constructor)]
     pub fn new() -> GameEngine {
         return GameEngine {
             current_field: None,
             config: None,
+            seed: 0,
             flag_count: 0,
-            first_click: true,
-            is_game_over: false,
+            probe_count: 0,
+            state: GameState::NotStarted,
             current_tool: ToolType::Shovel,
+            history: History::new(),
+            replay: None,
+            stats: GameStats::default(),
+            analysis: None,
             field_changes : Vec::new()
         };
     }
-    
+
     // Геттеры
-    #[wasm_bindgen(getter, js_name = "isGameOver")]
+    pub fn state(&self) -> GameState {
+        return self.state;
+    }
+
     pub fn is_game_over(&self) -> bool {
-        return self.is_game_over;
+        return self.state.is_over();
     }
 
-    #[wasm_bindgen(getter, js_name = "getQuantFlagCount")]
     pub fn quant_flags(&self) -> usize {
         return self.flag_count;
     }
 
-    #[wasm_bindgen(getter, js_name = "getCurrentTool")]
+    pub fn probes(&self) -> usize {
+        return self.probe_count;
+    }
+
     pub fn current_tool(&self) -> ToolType {
         return self.current_tool;
     }
 
-    #[wasm_bindgen(getter, js_name = "hasFieldNow")]
+    pub fn seed(&self) -> Result<u64, EngineError> {
+        self.config.as_ref().ok_or(EngineError::NoConfig)?;
+        return Ok(self.seed);
+    }
+
+    pub fn config(&self) -> Option<&GameConfig> {
+        return self.config.as_ref();
+    }
+
+    pub fn field(&self) -> Option<&Field> {
+        return self.current_field.as_ref();
+    }
+
     pub fn has_field_now(&self) -> bool {
         return self.current_field.is_some();
     }
 
-    #[wasm_bindgen(getter, js_name = "fieldWidth")]
-    pub fn width(&self) -> Result<u32, JsValue> {
-        return Ok(self.current_field.as_ref().ok_or(JsValue::from_str(UNDEFINED_FIELD))?.width);
+    pub fn width(&self) -> Result<u32, EngineError> {
+        return Ok(self.current_field.as_ref().ok_or(EngineError::NoField)?.width);
+    }
+
+    pub fn height(&self) -> Result<u32, EngineError> {
+        return Ok(self.current_field.as_ref().ok_or(EngineError::NoField)?.height);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        return self.undo_enabled() && self.history.can_undo();
+    }
+
+    pub fn can_redo(&self) -> bool {
+        return self.undo_enabled() && self.history.can_redo();
+    }
+
+    /// Запись текущей партии: сид, конфиг и все ходы.
+    pub fn replay(&self) -> Option<&Replay> {
+        return self.replay.as_ref();
+    }
+
+    /// Клетки, изменённые последним действием, с причинами (см. `TileChange`).
+    pub fn field_changes(&self) -> &[TileChange] {
+        return &self.field_changes;
+    }
+
+    /// Разбор поля (квантовый 3BV, угадывания, метка сложности); `None` до первого клика.
+    pub fn analysis(&self) -> Option<&BoardAnalysis> {
+        return self.analysis.as_ref();
+    }
+
+    /// Итог партии: счётчики, время, сложность поля и очки (см. `summary::score`).
+    /// Можно спросить и посреди игры, тогда время считается до текущего момента.
+    pub fn summary(&self) -> Result<GameSummary, EngineError> {
+        let config = self.config.as_ref().ok_or(EngineError::NoConfig)?;
+        let field = self.current_field.as_ref().ok_or(EngineError::NoField)?;
+        let won = self.state == GameState::Won;
+        let complexity = self.analysis.as_ref().map_or(0, |analysis| analysis.clicks);
+        let elapsed_ms = match self.stats.started_at {
+            Some(start) => (self.stats.finished_at.or(self.stats.paused_at).unwrap_or_else(now_ms) - start).max(0.0),
+            None => 0.0
+        };
+        let wrong_flags = field.tiles.iter().filter(|t| t.status == TileStatus::Flag && t.prob != Prob::MINE).count() as u32;
+        return Ok(GameSummary {
+            finished: self.state.is_over(),
+            won,
+            elapsed_ms,
+            clicks: self.stats.clicks,
+            opens: self.stats.opens,
+            flags: self.stats.flags,
+            wrong_flags,
+            quant_flags_used: self.stats.quant_flags,
+            quant_flags_left: self.flag_count as u32,
+            probes_used: config.probes.saturating_sub(self.probe_count as u32),
+            collapses: self.stats.collapses,
+            hints: self.stats.hints,
+            complexity,
+            difficulty: self.analysis.as_ref().and_then(BoardAnalysis::difficulty),
+            score: score(field, complexity, self.stats.clicks, won, self.flag_count as u32)
+        });
     }
 
-    #[wasm_bindgen(getter, js_name = "fieldHeight")]
-    pub fn height(&self) -> Result<u32, JsValue> {
-        return Ok(self.current_field.as_ref().ok_or(JsValue::from_str(UNDEFINED_FIELD))?.height);
+    /// Видимое состояние всего поля одним массивом, строка за строкой:
+    /// по `BOARD_VIEW_STRIDE` чисел на клетку - `TileView`, числитель и знаменатель суммы вокруг
+    /// (несократимая дробь, у неоткрытых клеток `0, 1`).
+    pub fn board_view(&self) -> Result<Vec<u16>, EngineError> {
+        let field = self.current_field.as_ref().ok_or(EngineError::NoField)?;
+        let mut result = Vec::with_capacity(field.tiles.len() * BOARD_VIEW_STRIDE);
+        for i in 0..field.tiles.len() {
+            let (x, y) = index_to_coords(field, i);
+            result.extend(self.tile_view(field, x, y)?);
+        }
+        return Ok(result);
     }
 
-    #[wasm_bindgen(getter, js_name = "fieldChanges")]
-    pub fn field_changes(&self) -> Array {
-        return Array::from_iter(self.field_changes.iter().map(|(x, y)| {
-            let obj = Object::new();
-            Reflect::set(&obj, &"x".into(), &JsValue::from(*x)).unwrap();
-            Reflect::set(&obj, &"y".into(), &JsValue::from(*y)).unwrap();
-            return obj;
-        }));
+    /// То же для клеток из `field_changes`: по `CHANGES_VIEW_STRIDE` чисел - `x, y`, причины
+    /// (`TileChange::flags`) и поля `board_view`.
+    pub fn changes_view(&self) -> Result<Vec<u16>, EngineError> {
+        let field = self.current_field.as_ref().ok_or(EngineError::NoField)?;
+        let mut result = Vec::with_capacity(self.field_changes.len() * CHANGES_VIEW_STRIDE);
+        for change in &self.field_changes {
+            result.extend([change.x as u16, change.y as u16, change.flags as u16]);
+            result.extend(self.tile_view(field, change.x, change.y)?);
+        }
+        return Ok(result);
     }
 
     // геттеры клеток
-    #[wasm_bindgen(js_name = "isTileMine")]
-    pub fn is_tile_mine(&self, x : i32, y : i32) -> Result<bool, JsValue> {
-        let field = self.current_field.as_ref().ok_or(UNDEFINED_FIELD)?;
-        let tile = field.get_tile(x, y).ok_or(format!("Tile ({}, {}) unfound", x, y))?;
-        return Ok(tile.prob == Prob(12));
+    pub fn is_tile_mine(&self, x : i32, y : i32) -> Result<bool, EngineError> {
+        let field = self.current_field.as_ref().ok_or(EngineError::NoField)?;
+        let tile = field.get_tile(x, y).ok_or(EngineError::OutOfBounds { x, y })?;
+        return Ok(tile.prob == Prob::MINE);
     }
 
-    #[wasm_bindgen(js_name = "getTileStatus")]
-    pub fn get_tile_status(&self, x : i32, y : i32) -> Result<TileStatus, JsValue> {
-        let field = self.current_field.as_ref().ok_or(UNDEFINED_FIELD)?;
-        let tile = field.get_tile(x, y).ok_or(format!("Tile ({}, {}) unfound", x, y))?;
+    pub fn get_tile_status(&self, x : i32, y : i32) -> Result<TileStatus, EngineError> {
+        let field = self.current_field.as_ref().ok_or(EngineError::NoField)?;
+        let tile = field.get_tile(x, y).ok_or(EngineError::OutOfBounds { x, y })?;
         return Ok(tile.status.clone());
     }
 
-    #[wasm_bindgen(js_name = "getProbabilityAroundTile")]
-    pub fn get_prob_around(&self, x : i32, y : i32) -> Result<u8, JsValue> {
-        let field = self.current_field.as_ref().ok_or(UNDEFINED_FIELD)?;
-        let prob = field.around_prob_sum(x, y)?;
-        return Ok(prob.0);
+    pub fn get_prob_around(&self, x : i32, y : i32) -> Result<Prob, EngineError> {
+        let field = self.current_field.as_ref().ok_or(EngineError::NoField)?;
+        return field.around_prob_sum(x, y);
+    }
+
+    /// Запутанный партнёр группы клетки: вид связи и клетки партнёра.
+    /// Открывается игроку только после измерения группы, до этого `None`.
+    pub fn entangled_partner(&self, x : i32, y : i32) -> Result<Option<Partner>, EngineError> {
+        let field = self.current_field.as_ref().ok_or(EngineError::NoField)?;
+        let tile = field.get_tile(x, y).ok_or(EngineError::OutOfBounds { x, y })?;
+        let Some(group) = tile.mine_id.filter(|_| tile.collapsed) else {
+            return Ok(None);
+        };
+        return Ok(field.partners(group).first().map(|&(partner, kind)| Partner { kind, tiles: field.get_group_elements(partner) }));
+    }
+
+    /// Центры и формы клеток для отрисовки, по индексу клетки (см. `Tiling::center`).
+    pub fn layout(&self) -> Result<Vec<(f64, f64, TileShape)>, EngineError> {
+        let field = self.current_field.as_ref().ok_or(EngineError::NoField)?;
+        return Ok((0..field.tiles.len()).map(|i| {
+            let (x, y) = index_to_coords(field, i);
+            let (cx, cy) = field.tiling.center(x, y);
+            return (cx, cy, field.tiling.shape(x, y));
+        }).collect());
+    }
+
+    /// Что игрок может вывести из открытых чисел (см. `Solver`).
+    pub fn deductions(&self) -> Result<Vec<Deduction>, EngineError> {
+        let field = self.current_field.as_ref().ok_or(EngineError::NoField)?;
+        return Ok(field.deductions());
+    }
+
+    /// Вероятность мины для каждой клетки с точки зрения игрока, по индексу клетки (`None` - открыта).
+    /// Как она считается, см. `Odds::mine_odds`.
+    pub fn mine_odds(&self) -> Result<Vec<Option<f64>>, EngineError> {
+        let config = self.config.as_ref().ok_or(EngineError::NoConfig)?;
+        let field = self.current_field.as_ref().ok_or(EngineError::NoField)?;
+        let (groups, candidates) = group_counts(field.tiles.len(), config.groups, config.candidates);
+        return Ok(field.mine_odds(groups, candidates));
     }
 
-    // экспортируемые методы для привязки
-    #[wasm_bindgen(js_name = "startNewGame")]
-    pub fn start_new_game(&mut self, width : u32, height : u32, groups : f64, candidates : f64) -> Result<(), JsValue> {
-        self.set_config(width, height, groups, candidates);
+    // основные действия
+    pub fn start_new_game(&mut self, config : GameConfig) -> Result<(), EngineError> {
+        config.validate()?;
+        // без сида берём случайный, но всё равно запоминаем его, чтобы игру можно было воспроизвести
+        self.seed = config.seed.unwrap_or_else(|| fastrand::u64(..));
+        self.replay = Some(Replay::new(GameConfig { seed: Some(self.seed), ..config.clone() }));
+        self.config = Some(config);
         self.initialize_field()?;
         self.flag_count = self.calculate_flag_count()?;
-        self.first_click = true;
-        self.is_game_over = false;
-        self.field_changes = (0..width*height).map(|i| ((i % width) as i32, (i / width) as i32)).collect();
+        self.probe_count = self.config.as_ref().map_or(0, |config| config.probes as usize);
+        self.state = GameState::NotStarted;
+        self.history.clear();
+        self.stats = GameStats::default();
+        self.analysis = None;
+        self.field_changes = self.current_field.as_ref().map_or(Vec::new(), all_changes);
+        return Ok(());
+    }
+
+    pub fn tile_interact(&mut self, x : i32, y : i32) -> Result<(), EngineError> {
+        // до первого клика можно только копать: поля ещё нет
+        self.check_state(self.current_tool == ToolType::Shovel)?;
+        let snapshot = self.snapshot();
+        // в запись партии, историю и счётчик кликов идут только принятые ходы
+        if let Err(e) = self.interact(x, y) {
+            self.rollback(snapshot);
+            return Err(e);
+        }
+        self.record(snapshot);
+        self.record_move(Move::Interact { x, y, tool: self.current_tool });
+        self.stats.clicks += 1;
+        return Ok(());
+    }
+
+    pub fn collapse_quant_flags(&mut self) -> Result<(), EngineError> {
+        self.check_state(false)?;
+        let snapshot = self.snapshot();
+        if let Err(e) = self.collapse_flagged_groups() {
+            self.rollback(snapshot);
+            return Err(e);
+        }
+        self.record(snapshot);
+        self.record_move(Move::Collapse);
+        if !self.field_changes.is_empty() {
+            self.stats.collapses += 1;
+        }
+        return Ok(());
+    }
+
+    pub fn change_tool(&mut self, tool : ToolType) {
+        self.current_tool = tool;
+    }
+
+    /// Подсказка следующего хода (см. `Hinter`). Если в конфиге задана цена, списывает
+    /// квантовые флажки; подсказка попадает в запись партии, но не в историю: отмена не возвращает флажки.
+    pub fn hint(&mut self) -> Result<Hint, EngineError> {
+        self.check_state(true)?;
+        let config = self.config.as_ref().ok_or(EngineError::NoConfig)?;
+        let field = self.current_field.as_ref().ok_or(EngineError::NoField)?;
+        let cost = config.hint_cost as usize;
+        if self.flag_count < cost {
+            return Err(EngineError::NotEnoughFlags { needed: cost, left: self.flag_count });
+        }
+        let hint = if self.state == GameState::NotStarted {
+            Hint { x: (field.width / 2) as i32, y: (field.height / 2) as i32, action: HintAction::Open, reason: HintReason::FirstClick }
+        } else {
+            let (groups, candidates) = group_counts(field.tiles.len(), config.groups, config.candidates);
+            field.hint(groups, candidates, self.flag_count > cost).ok_or(EngineError::NoHint)?
+        };
+        self.record_move(Move::Hint);
+        self.flag_count -= cost;
+        self.stats.hints += 1;
+        self.field_changes.clear();
+        return Ok(hint);
+    }
+
+    /// Отменяет последнее действие. `Ok(false)`, если отменять нечего.
+    pub fn undo(&mut self) -> Result<bool, EngineError> {
+        if !self.undo_enabled() { return Err(EngineError::UndoDisabled); }
+        if self.state == GameState::Paused { return Err(EngineError::Paused); }
+        let snapshot = self.snapshot();
+        let field = self.current_field.as_mut().ok_or(EngineError::NoField)?;
+        return match self.history.undo(field) {
+            Some((state, generation)) => { self.restore(state, generation, snapshot); self.record_move(Move::Undo); Ok(true) },
+            None => { field.take_journal(); Ok(false) }
+        };
+    }
+
+    /// Повторяет последнее отменённое действие. `Ok(false)`, если повторять нечего.
+    pub fn redo(&mut self) -> Result<bool, EngineError> {
+        if !self.undo_enabled() { return Err(EngineError::UndoDisabled); }
+        if self.state == GameState::Paused { return Err(EngineError::Paused); }
+        let snapshot = self.snapshot();
+        let field = self.current_field.as_mut().ok_or(EngineError::NoField)?;
+        return match self.history.redo(field) {
+            Some((state, generation)) => { self.restore(state, generation, snapshot); self.record_move(Move::Redo); Ok(true) },
+            None => { field.take_journal(); Ok(false) }
+        };
+    }
+
+    /// Останавливает таймер партии; до `resume` ходы отклоняются с `EngineError::Paused`.
+    pub fn pause(&mut self) -> Result<(), EngineError> {
+        self.check_state(false)?;
+        self.state = GameState::Paused;
+        self.stats.paused_at = Some(now_ms());
+        return Ok(());
+    }
+
+    /// Продолжает партию после `pause`; время паузы в итог не идёт.
+    pub fn resume(&mut self) -> Result<(), EngineError> {
+        if self.state != GameState::Paused {
+            return Err(EngineError::NotPaused);
+        }
+        self.state = GameState::Playing;
+        if let (Some(paused), Some(started)) = (self.stats.paused_at.take(), self.stats.started_at.as_mut()) {
+            *started += (now_ms() - paused).max(0.0);
+        }
+        return Ok(());
+    }
+
+    /// Сохраняет всю сессию (поле, конфиг, инструмент, флажки и состояние генератора) в байты.
+    pub fn save_game(&self) -> Vec<u8> {
+        return self.to_bytes();
+    }
+
+    /// Восстанавливает сессию из `save_game`. При ошибке текущая игра не меняется.
+    pub fn load_game(&mut self, bytes : &[u8]) -> Result<(), EngineError> {
+        *self = GameEngine::from_bytes(bytes)?;
         return Ok(());
     }
 
-    #[wasm_bindgen(js_name = "handleTileInteraction")]
-    pub fn tile_interact(&mut self, x : i32, y : i32) -> Result<(), JsValue> {
-        if self.is_game_over { return Ok(()); }
+    // внутренние методы
+    fn interact(&mut self, x : i32, y : i32) -> Result<(), EngineError> {
         match self.current_tool {
             ToolType::Shovel => self.open_tile(x, y),
+            ToolType::Probe => self.probe_tile(x, y),
             _ => self.toggle_flag(x, y)
-        }.map_err(|e| JsValue::from(e))?;
+        }?;
         self.check_win()?;
-        if self.is_game_over {
-            let field = self.current_field.as_ref().ok_or(UNDEFINED_FIELD)?;
-            self.field_changes.extend(
-                (0..field.width * field.height).map(|i| ((i % field.width) as i32, (i / field.width) as i32))
-                .filter(|(x, y)| {
-                    let tile = field.get_tile(*x, *y).unwrap_or(&NON_FLAGGED);
-                    return tile.status == TileStatus::Flag && tile.prob >= Prob(12);})
-            ); // добавляет в изменения все клетки с флажками
+        if self.state.is_over() {
+            self.stats.finished_at.get_or_insert_with(now_ms);
         }
         return Ok(());
     }
 
-    #[wasm_bindgen(js_name = "collapseQuantFlags")]
-    pub fn collapse_quant_flags(&mut self) -> Result<(), JsValue> {
-        let field = self.current_field.as_mut().ok_or(UNDEFINED_FIELD)?;
-        self.field_changes.clear();
-        self.field_changes.extend(field.collapse_quant_flags()?);
+    fn collapse_flagged_groups(&mut self) -> Result<(), EngineError> {
+        let field = self.current_field.as_mut().ok_or(EngineError::NoField)?;
+        field.collapse_quant_flags()?;
         return Ok(());
     }
 
-    #[wasm_bindgen(js_name = "changeTool")]
-    pub fn change_tool(&mut self, tool : ToolType) {
-        self.current_tool = tool;
+    fn record_move(&mut self, game_move : Move) {
+        if let Some(replay) = self.replay.as_mut() {
+            replay.push(game_move);
+        }
     }
 
-    // внутренние методы
-    fn open_tile(&mut self, x : i32, y : i32) -> Result<(), String> {
-        let config = self.config.as_ref().ok_or(UNDEFINED_CONFIG)?;
-        let field = self.current_field.as_mut().ok_or(UNDEFINED_FIELD)?;
+    fn tile_view(&self, field : &Field, x : i32, y : i32) -> Result<[u16; BOARD_VIEW_STRIDE], EngineError> {
+        let tile = field.get_tile(x, y).ok_or(EngineError::OutOfBounds { x, y })?;
+        let is_mine = tile.prob == Prob::MINE;
+        let view = match tile.status {
+            TileStatus::None => TileView::Closed,
+            TileStatus::QuantFlag => TileView::QuantFlag,
+            TileStatus::Flag if self.is_game_over() && is_mine => TileView::RightFlag,
+            TileStatus::Flag => TileView::Flag,
+            TileStatus::Opened if is_mine => TileView::Mine,
+            TileStatus::Opened => {
+                let (num, den) = field.around_prob_sum(x, y)?.reduced();
+                return Ok([TileView::Opened as u16, num as u16, den as u16]);
+            }
+        };
+        return Ok([view as u16, 0, 1]);
+    }
+
+    // ходы разрешены только во время игры, а с `allow_not_started` - и до первого клика
+    fn check_state(&self, allow_not_started : bool) -> Result<(), EngineError> {
+        match self.state {
+            GameState::Playing => return Ok(()),
+            GameState::NotStarted if allow_not_started => return Ok(()),
+            GameState::NotStarted => return Err(EngineError::NotStarted),
+            GameState::Paused => return Err(EngineError::Paused),
+            GameState::Won | GameState::Lost { .. } => return Err(EngineError::GameOver)
+        }
+    }
+
+    fn undo_enabled(&self) -> bool {
+        return self.config.as_ref().is_some_and(|config| config.undo);
+    }
+
+    // флажки, потраченные на подсказки: они не возвращаются отменой
+    fn hint_spent(&self) -> usize {
+        return self.config.as_ref().map_or(0, |config| config.hint_cost as usize) * self.stats.hints as usize;
+    }
+
+    // флажки в истории хранятся вместе с потраченными на подсказки, см. `restore`
+    fn engine_state(&self) -> Option<EngineState> {
+        let field = self.current_field.as_ref()?;
+        return Some(EngineState {
+            flag_count: self.flag_count + self.hint_spent(),
+            probe_count: self.probe_count,
+            state: self.state,
+            probed: field.probed,
+            rng_seed: field.rng.get_seed()
+        });
+    }
+
+    // состояние до действия; клетки, которые оно тронет, пишет журнал поля (см. `Field::start_journal`).
+    // По ним считаются изменения и запись истории, без копии и сравнения всего поля
+    fn snapshot(&mut self) -> Option<EngineState> {
+        let state = self.engine_state()?;
+        self.current_field.as_mut()?.start_journal();
+        return Some(state);
+    }
+
+    fn record(&mut self, snapshot : Option<EngineState>) {
+        let Some(before) = snapshot else { return; };
+        let (Some(after), undo) = (self.engine_state(), self.undo_enabled()) else { return; };
+        let Some(field) = self.current_field.as_mut() else { return; };
+        let touched = field.take_journal();
+        self.field_changes = diff_changes(&touched, field, before.state.is_over() != after.state.is_over());
+        if !undo { return; }
+        let generation = (before.state == GameState::NotStarted && after.state != GameState::NotStarted).then(|| Generation {
+            entanglement: field.entanglement.clone(),
+            group_tiles: field.group_tiles.clone(),
+            outcomes: field.outcomes.clone(),
+            analysis: self.analysis.clone(),
+            started_at: self.stats.started_at
+        });
+        if let Some(entry) = HistoryEntry::diff(&touched, before, field, after, generation) {
+            self.history.push(entry);
+        }
+    }
+
+    // отклонённый ход не оставляет следов: клетки, генератор поля и состояние движка возвращаются
+    // к снимку до хода, поэтому запись партии без него воспроизводит ту же игру
+    fn rollback(&mut self, snapshot : Option<EngineState>) {
+        let Some(before) = snapshot else { return; };
+        let Some(field) = self.current_field.as_mut() else { return; };
+        for (i, tile) in field.take_journal() {
+            field.tiles[i] = tile;
+        }
+        field.rng.seed(before.rng_seed);
+        field.probed = before.probed;
+        self.flag_count = before.flag_count.saturating_sub(self.hint_spent());
+        self.probe_count = before.probe_count;
+        self.state = before.state;
         self.field_changes.clear();
+    }
 
-        if self.first_click {
-            field.generate(x, y, config.groups, config.candidates)?;
-            self.first_click = false;
+    fn restore(&mut self, state : EngineState, generation : Option<Generation>, snapshot : Option<EngineState>) {
+        self.flag_count = state.flag_count.saturating_sub(self.hint_spent());
+        self.probe_count = state.probe_count;
+        self.state = state.state;
+        if !self.state.is_over() {
+            self.stats.finished_at = None; // отмена проигрыша - партия продолжается
+        }
+        let (Some(before), Some(field)) = (snapshot, self.current_field.as_mut()) else { return; };
+        field.probed = state.probed;
+        if let Some(generation) = generation {
+            field.entanglement = generation.entanglement;
+            field.group_tiles = generation.group_tiles;
+            field.outcomes = generation.outcomes;
+            self.analysis = generation.analysis;
+            self.stats.started_at = generation.started_at;
         }
+        let touched = field.take_journal();
+        self.field_changes = diff_changes(&touched, field, before.state.is_over() != self.state.is_over());
+    }
 
-        if !field.open_tile(x, y)? {
-            self.field_changes.extend(field.multiopen(x, y)?);
+    // лопата по открытой клетке - аккорд (см. `TileOpener::chord`)
+    fn open_tile(&mut self, x : i32, y : i32) -> Result<(), EngineError> {
+        let config = self.config.as_ref().ok_or(EngineError::NoConfig)?;
+        let field = self.current_field.as_mut().ok_or(EngineError::NoField)?;
+        let status = field.get_tile(x, y).ok_or(EngineError::OutOfBounds { x, y })?.status.clone();
+
+        if self.state == GameState::NotStarted {
+            if config.no_guess {
+                field.generate_no_guess(x, y, config.groups, config.candidates, self.flag_count, config.generation_attempts as usize)?;
+            } else {
+                field.generate(x, y, config.groups, config.candidates)?;
+            }
+            self.state = GameState::Playing;
+            // сид генератора читается без сдвига, чтобы разбор не менял ход игры
+            self.analysis = Some(field.analyze(x, y, self.flag_count, field.rng.get_seed()));
+            self.stats.started_at = Some(now_ms());
+        }
+        // аккорд, который ничего не открыл, в открытия не идёт
+        let opened = if status == TileStatus::Opened {
+            let chord = field.chord(x, y)?;
+            if let Some((mx, my)) = chord.exploded {
+                self.state = GameState::Lost { x: mx, y: my };
+            }
+            !chord.changed.is_empty()
         } else {
-            self.field_changes.push((x, y));
-            self.is_game_over = true;
+            if !field.open_tile(x, y)? {
+                field.multiopen(x, y)?;
+            } else {
+                self.state = GameState::Lost { x, y };
+            }
+            true
+        };
+
+        if opened {
+            self.stats.opens += 1;
         }
+        return Ok(());
+    }
+
+    // щуп тратит одну пробу: под миной ставит флажок, пустую клетку открывает с каскадом
+    fn probe_tile(&mut self, x : i32, y : i32) -> Result<(), EngineError> {
+        let field = self.current_field.as_mut().ok_or(EngineError::NoField)?;
+        let tile = field.get_tile(x, y).ok_or(EngineError::OutOfBounds { x, y })?;
+        if tile.status != TileStatus::None {
+            return Err(EngineError::AlreadyOpened { x, y });
+        }
+        if tile.collapsed {
+            return Err(EngineError::AlreadyCollapsed { x, y });
+        }
+        if self.probe_count == 0 {
+            return Err(EngineError::NotEnoughProbes);
+        }
+        self.probe_count -= 1;
 
+        if field.probe(x, y)? {
+            field.set_tile_status(x, y, TileStatus::Flag);
+        } else {
+            field.open_tile(x, y)?;
+            field.multiopen(x, y)?;
+        }
         return Ok(());
     }
 
-    fn toggle_flag(&mut self, x : i32, y : i32) -> Result<(), String> {
-        self.field_changes.clear();
-        if self.first_click {return Ok(());}
-        let field = self.current_field.as_mut().ok_or(UNDEFINED_FIELD)?;
-        match field.get_tile(x, y).ok_or(format!("Unfound tile ({}, {})", x, y))?.status {
+    fn toggle_flag(&mut self, x : i32, y : i32) -> Result<(), EngineError> {
+        let field = self.current_field.as_mut().ok_or(EngineError::NoField)?;
+        match field.get_tile(x, y).ok_or(EngineError::OutOfBounds { x, y })?.status {
             TileStatus::Opened => { return Ok(()); },
             TileStatus::Flag => {
                 field.set_tile_status(x, y, TileStatus::None);
@@ -200,40 +844,44 @@ impl GameEngine {
                 if self.current_tool == ToolType::QuantFlag {
                     if self.flag_count > 0 {
                         self.flag_count -= 1;
+                        self.stats.quant_flags += 1;
                         field.set_tile_status(x, y, TileStatus::QuantFlag);
                     }
                 }
                 else {
+                    self.stats.flags += 1;
                     field.set_tile_status(x, y, TileStatus::Flag);
                 }
             },
         }
-        self.field_changes.push((x, y));
         return Ok(());
     }
 
-    fn set_config(&mut self, width : u32, height : u32, groups : f64, candidates : f64) {
-        self.config = Some(GameConfig {width, height, groups, candidates})
-    }
-
-    fn initialize_field(&mut self) -> Result<(), &'static str> {
-        let config = self.config.as_ref().ok_or("Config isnt defined")?;
-        self.current_field = Some(Field::new(
-            config.width, 
-            config.height
-        ));
+    fn initialize_field(&mut self) -> Result<(), EngineError> {
+        let config = self.config.as_ref().ok_or(EngineError::NoConfig)?;
+        let mut field = Field::with_shape(
+            config.width,
+            config.height,
+            self.seed,
+            config.topology,
+            config.tiling
+        );
+        field.group_sizes = config.group_sizes();
+        field.group_mines = config.group_mines as usize;
+        field.entangled = config.entangled;
+        self.current_field = Some(field);
         return Ok(());
     }
 
-    fn calculate_flag_count(&self) -> Result<usize, &'static str> {
-        let config = self.config.as_ref().ok_or("Config isnt defined")?;
-        return Ok( (config.width as f64 * config.height as f64 * config.groups * 1.3) as usize );
+    fn calculate_flag_count(&self) -> Result<usize, EngineError> {
+        let config = self.config.as_ref().ok_or(EngineError::NoConfig)?;
+        return Ok(config.quant_flag_count());
     }
 
-    fn check_win(&mut self) -> Result<(), &'static str> {
-        let field = self.current_field.as_ref().ok_or(UNDEFINED_FIELD)?;
-        if field.is_win() {
-            self.is_game_over = true;
+    fn check_win(&mut self) -> Result<(), EngineError> {
+        let field = self.current_field.as_ref().ok_or(EngineError::NoField)?;
+        if self.state == GameState::Playing && field.is_win() {
+            self.state = GameState::Won;
         }
         return Ok(());
     }
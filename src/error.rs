@@ -0,0 +1,103 @@
+use std::fmt;
+
+use crate::tile::GroupId;
+
+/// Ошибки движка. `code()` - стабильный идентификатор для интерфейса (JS получает его в поле `code`),
+/// `Display` - сообщение для логов на английском.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineError {
+    NoField,
+    NoConfig,
+    OutOfBounds { x : i32, y : i32 },
+    AlreadyOpened { x : i32, y : i32 }, // клетка открыта или на ней флажок
+    AlreadyCollapsed { x : i32, y : i32 },
+    GroupNotFound(GroupId),
+    GroupCollapsed(GroupId),
+    InvalidConfig(String),
+    GenerationFailed { attempts : usize },
+    GameOver,
+    NotStarted, // действие требует поля, а первого клика ещё не было
+    Paused,
+    NotPaused,
+    UndoDisabled,
+    NotEnoughFlags { needed : usize, left : usize },
+    NotEnoughProbes, // щупы на эту игру кончились
+    NoHint,
+    CorruptedSave(String),
+    UnsupportedVersion { found : u8, expected : u8 },
+    InvalidReplay(String)
+}
+
+impl EngineError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            EngineError::NoField => return "noField",
+            EngineError::NoConfig => return "noConfig",
+            EngineError::OutOfBounds { .. } => return "outOfBounds",
+            EngineError::AlreadyOpened { .. } => return "alreadyOpened",
+            EngineError::AlreadyCollapsed { .. } => return "alreadyCollapsed",
+            EngineError::GroupNotFound(_) => return "groupNotFound",
+            EngineError::GroupCollapsed(_) => return "groupCollapsed",
+            EngineError::InvalidConfig(_) => return "invalidConfig",
+            EngineError::GenerationFailed { .. } => return "generationFailed",
+            EngineError::GameOver => return "gameOver",
+            EngineError::NotStarted => return "notStarted",
+            EngineError::Paused => return "paused",
+            EngineError::NotPaused => return "notPaused",
+            EngineError::UndoDisabled => return "undoDisabled",
+            EngineError::NotEnoughFlags { .. } => return "notEnoughFlags",
+            EngineError::NotEnoughProbes => return "notEnoughProbes",
+            EngineError::NoHint => return "noHint",
+            EngineError::CorruptedSave(_) => return "corruptedSave",
+            EngineError::UnsupportedVersion { .. } => return "unsupportedVersion",
+            EngineError::InvalidReplay(_) => return "invalidReplay"
+        }
+    }
+
+    /// Клетка, к которой относится ошибка, если есть.
+    pub fn coords(&self) -> Option<(i32, i32)> {
+        match self {
+            EngineError::OutOfBounds { x, y }
+            | EngineError::AlreadyOpened { x, y }
+            | EngineError::AlreadyCollapsed { x, y } => return Some((*x, *y)),
+            _ => return None
+        }
+    }
+
+    /// Группа, к которой относится ошибка, если есть.
+    pub fn group(&self) -> Option<GroupId> {
+        match self {
+            EngineError::GroupNotFound(group) | EngineError::GroupCollapsed(group) => return Some(*group),
+            _ => return None
+        }
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::NoField => return write!(f, "Field isn't defined"),
+            EngineError::NoConfig => return write!(f, "Config isn't defined"),
+            EngineError::OutOfBounds { x, y } => return write!(f, "Tile ({}, {}) is outside the field", x, y),
+            EngineError::AlreadyOpened { x, y } => return write!(f, "Tile ({}, {}) already opened or there is a flag on it", x, y),
+            EngineError::AlreadyCollapsed { x, y } => return write!(f, "Tile ({}, {}) has been already collapsed", x, y),
+            EngineError::GroupNotFound(group) => return write!(f, "Group ({}) has no tiles", group),
+            EngineError::GroupCollapsed(group) => return write!(f, "Group ({}) has been already collapsed", group),
+            EngineError::InvalidConfig(reason) => return write!(f, "Invalid game config: {}", reason),
+            EngineError::GenerationFailed { attempts } => return write!(f, "No board without guessing found in {} attempts", attempts),
+            EngineError::GameOver => return write!(f, "Game is over"),
+            EngineError::NotStarted => return write!(f, "Game hasn't started yet, the first move must be a dig"),
+            EngineError::Paused => return write!(f, "Game is paused"),
+            EngineError::NotPaused => return write!(f, "Game isn't paused"),
+            EngineError::UndoDisabled => return write!(f, "Undo is disabled in this game"),
+            EngineError::NotEnoughFlags { needed, left } => return write!(f, "Needs {} quantum flags, only {} left", needed, left),
+            EngineError::NotEnoughProbes => return write!(f, "No probes left"),
+            EngineError::NoHint => return write!(f, "There is no move to suggest"),
+            EngineError::CorruptedSave(reason) => return write!(f, "Save data is corrupted: {}", reason),
+            EngineError::UnsupportedVersion { found, expected } => return write!(f, "Unsupported save version ({}), expected {}", found, expected),
+            EngineError::InvalidReplay(reason) => return write!(f, "Invalid replay: {}", reason)
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
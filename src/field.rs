@@ -1,19 +1,353 @@
+use std::ops::RangeInclusive;
+
+use fastrand::Rng;
+
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+use crate::changes::Journal;
+use crate::error::EngineError;
+use crate::generator::{DEFAULT_GROUP_SIZES, MAX_GROUP_SIZE};
 use crate::tile::*;
 
 pub const DIRECTIONS : [(i32, i32); 8] = [(1,0), (-1,0), (0,1), (0,-1), (1,1), (1,-1), (-1,1), (-1,-1)];
 
+/// Как склеены края поля.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Topology {
+    Rectangle, // края не склеены
+    Cylinder,  // левый край склеен с правым
+    Torus      // склеены обе пары краёв
+}
+
+impl Topology {
+    pub fn to_u8(&self) -> u8 {
+        return *self as u8;
+    }
+
+    pub fn from_u8(value : u8) -> Result<Topology, EngineError> {
+        match value {
+            0 => return Ok(Topology::Rectangle),
+            1 => return Ok(Topology::Cylinder),
+            2 => return Ok(Topology::Torus),
+            _ => return Err(EngineError::CorruptedSave(format!("unknown topology ({})", value)))
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Topology::Rectangle => return "rectangle",
+            Topology::Cylinder => return "cylinder",
+            Topology::Torus => return "torus"
+        }
+    }
+
+    pub fn from_name(name : &str) -> Option<Topology> {
+        return [Topology::Rectangle, Topology::Cylinder, Topology::Torus].into_iter().find(|t| t.name() == name);
+    }
+
+    // склеены ли края по x и по y
+    fn wraps(&self) -> (bool, bool) {
+        match self {
+            Topology::Rectangle => return (false, false),
+            Topology::Cylinder => return (true, false),
+            Topology::Torus => return (true, true)
+        }
+    }
+
+    // переносит координату через склеенные края; `None`, если клетка за краем
+    fn wrap(&self, x : i32, y : i32, width : u32, height : u32) -> Option<(i32, i32)> {
+        let (width, height) = (width as i32, height as i32);
+        let (wrap_x, wrap_y) = self.wraps();
+        let x = if wrap_x { x.rem_euclid(width) } else { x };
+        let y = if wrap_y { y.rem_euclid(height) } else { y };
+        return (0 <= x && 0 <= y && x < width && y < height).then_some((x, y));
+    }
+
+    /// Соседи клетки по смещениям `directions` без повторов и без самой клетки:
+    /// на узком склеенном поле разные направления могут вести в одну и ту же клетку.
+    pub fn neighbours(&self, directions : &[(i32, i32)], x : i32, y : i32, width : u32, height : u32) -> Vec<(i32, i32)> {
+        let mut result = Vec::with_capacity(directions.len());
+        for &(dx, dy) in directions {
+            if let Some(coords) = self.wrap(x + dx, y + dy, width, height) {
+                if coords != (x, y) && !result.contains(&coords) {
+                    result.push(coords);
+                }
+            }
+        }
+        return result;
+    }
+}
+
+// шестиугольники в осевых координатах (q = x, r = y), поле - ромб
+const HEX_DIRECTIONS : [(i32, i32); 6] = [(1,0), (-1,0), (0,1), (0,-1), (1,-1), (-1,1)];
+// треугольники: в строке чередуются ▲ и ▼ ((x + y) чётное - ▲); соседи - все клетки с общей вершиной
+const TRIANGLE_UP_DIRECTIONS : [(i32, i32); 12] = [
+    (-1,-1), (0,-1), (1,-1),
+    (-2,0), (-1,0), (1,0), (2,0),
+    (-2,1), (-1,1), (0,1), (1,1), (2,1)
+];
+const TRIANGLE_DOWN_DIRECTIONS : [(i32, i32); 12] = [
+    (-2,-1), (-1,-1), (0,-1), (1,-1), (2,-1),
+    (-2,0), (-1,0), (1,0), (2,0),
+    (-1,1), (0,1), (1,1)
+];
+
+/// Форма клеток поля.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tiling {
+    Square,  // 8 соседей
+    Hex,     // 6 соседей, осевые координаты
+    Triangle // 12 соседей
+}
+
+/// Форма конкретной клетки для отрисовки.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileShape {
+    Square,
+    Hex,
+    TriangleUp,
+    TriangleDown
+}
+
+impl Tiling {
+    pub fn to_u8(&self) -> u8 {
+        return *self as u8;
+    }
+
+    pub fn from_u8(value : u8) -> Result<Tiling, EngineError> {
+        match value {
+            0 => return Ok(Tiling::Square),
+            1 => return Ok(Tiling::Hex),
+            2 => return Ok(Tiling::Triangle),
+            _ => return Err(EngineError::CorruptedSave(format!("unknown tiling ({})", value)))
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tiling::Square => return "square",
+            Tiling::Hex => return "hex",
+            Tiling::Triangle => return "triangle"
+        }
+    }
+
+    pub fn from_name(name : &str) -> Option<Tiling> {
+        return [Tiling::Square, Tiling::Hex, Tiling::Triangle].into_iter().find(|t| t.name() == name);
+    }
+
+    /// Смещения к соседям клетки (у треугольников зависят от её ориентации).
+    pub fn directions(&self, x : i32, y : i32) -> &'static [(i32, i32)] {
+        match self {
+            Tiling::Square => return &DIRECTIONS,
+            Tiling::Hex => return &HEX_DIRECTIONS,
+            Tiling::Triangle => return match self.shape(x, y) {
+                TileShape::TriangleUp => &TRIANGLE_UP_DIRECTIONS,
+                _ => &TRIANGLE_DOWN_DIRECTIONS
+            }
+        }
+    }
+
+    pub fn shape(&self, x : i32, y : i32) -> TileShape {
+        match self {
+            Tiling::Square => return TileShape::Square,
+            Tiling::Hex => return TileShape::Hex,
+            Tiling::Triangle => return if (x + y).rem_euclid(2) == 0 { TileShape::TriangleUp } else { TileShape::TriangleDown }
+        }
+    }
+
+    /// Центр клетки на плоскости. Единица - сторона квадрата, радиус описанной окружности
+    /// шестиугольника (вершиной вверх) или сторона треугольника. Левый верхний угол поля около (0, 0).
+    pub fn center(&self, x : i32, y : i32) -> (f64, f64) {
+        let (x, y) = (x as f64, y as f64);
+        let sqrt3 = 3f64.sqrt();
+        match self.shape(x as i32, y as i32) {
+            TileShape::Square => return (x + 0.5, y + 0.5),
+            TileShape::Hex => return (sqrt3 * (x + y / 2.0 + 0.5), 1.5 * y + 1.0),
+            TileShape::TriangleUp => return ((x + 1.0) / 2.0, sqrt3 / 2.0 * (y + 2.0 / 3.0)),
+            TileShape::TriangleDown => return ((x + 1.0) / 2.0, sqrt3 / 2.0 * (y + 1.0 / 3.0))
+        }
+    }
+
+    /// Проверяет, что поле такой формы можно склеить по `topology`:
+    /// у треугольников склеенная сторона должна быть чётной, иначе ▲ встретится с ▲.
+    pub fn check_size(&self, width : u32, height : u32, topology : Topology) -> Result<(), EngineError> {
+        if *self != Tiling::Triangle { return Ok(()); }
+        let (wrap_x, wrap_y) = topology.wraps();
+        if wrap_x && !width.is_multiple_of(2) {
+            return Err(EngineError::InvalidConfig(String::from("wrapped triangle board needs an even width")));
+        }
+        if wrap_y && !height.is_multiple_of(2) {
+            return Err(EngineError::InvalidConfig(String::from("wrapped triangle board needs an even height")));
+        }
+        return Ok(());
+    }
+}
+
+/// Как исход измерения группы определяет исход её запутанного партнёра.
+/// Позиция - номер клетки в группе по порядку клеток поля.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Entanglement {
+    Mirror, // мина партнёра на той же позиции
+    Reverse // мина партнёра на позиции с конца
+}
+
+impl Entanglement {
+    pub fn to_u8(&self) -> u8 {
+        return *self as u8;
+    }
+
+    pub fn from_u8(value : u8) -> Result<Entanglement, EngineError> {
+        match value {
+            0 => return Ok(Entanglement::Mirror),
+            1 => return Ok(Entanglement::Reverse),
+            _ => return Err(EngineError::CorruptedSave(format!("unknown entanglement ({})", value)))
+        }
+    }
+
+    /// Позиции мин в партнёре из `size` клеток по позициям мин измеренной группы.
+    /// Совпавшие позиции сдвигаются к следующей свободной, чтобы мины не сливались.
+    pub fn map(&self, positions : &[usize], size : usize) -> Vec<usize> {
+        let mut result = Vec::new();
+        for &position in positions.iter().take(size) {
+            let mut target = match self {
+                Entanglement::Mirror => position % size,
+                Entanglement::Reverse => size - 1 - position % size
+            };
+            while result.contains(&target) {
+                target = (target + 1) % size;
+            }
+            result.push(target);
+        }
+        return result;
+    }
+}
+
+/// Запутанный партнёр измеренной группы, как его видит игрок.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Partner {
+    pub kind : Entanglement,
+    pub tiles : Vec<(i32, i32)>
+}
+
+/// Проверяет параметры групп: размеры от 1 до `MAX_GROUP_SIZE` (минимум не больше максимума),
+/// мин в группе от 1 до минимального размера.
+pub fn check_groups(sizes : &RangeInclusive<usize>, mines : usize) -> Result<(), EngineError> {
+    if *sizes.start() < 1 || sizes.is_empty() || *sizes.end() > MAX_GROUP_SIZE {
+        return Err(EngineError::InvalidConfig(format!("group sizes must be within 1..={}, got {}..={}",
+            MAX_GROUP_SIZE, sizes.start(), sizes.end())));
+    }
+    if mines < 1 || mines > *sizes.start() {
+        return Err(EngineError::InvalidConfig(format!("mines per group must be within 1..={}, got {}", sizes.start(), mines)));
+    }
+    return Ok(());
+}
+
+#[derive(Clone)]
 pub struct Field {
     pub width: u32,
     pub height: u32,
-    pub tiles: Vec<Tile>
+    pub tiles: Vec<Tile>,
+    pub topology: Topology,
+    pub tiling: Tiling,
+    pub group_sizes: RangeInclusive<usize>, // допустимые размеры групп, игрок их знает
+    pub group_mines: usize, // сколько мин в каждой группе
+    pub entangled: f64, // доля групп, которые генератор объединяет в запутанные пары
+    pub entanglement: Vec<(GroupId, GroupId, Entanglement)>, // рёбра графа запутанности между группами (скрыты от игрока)
+    pub group_tiles: Vec<Vec<usize>>, // клетки каждой группы по возрастанию индекса; вынутая щупом клетка остаётся в списке
+    pub probed: bool, // были ли слабые измерения: после них у групп бывают любые доли мин
+    pub outcomes: Vec<bool>, // мины исхода измерений, зафиксированного генерацией без угадывания; пусто - исходы случайны
+    pub journal: Journal, // клетки, тронутые текущим действием (см. `start_journal`)
+    pub rng: Rng // все случайные значения поля берутся отсюда, чтобы игру можно было повторить по сиду
 }
 
 impl Field {
-    pub fn new(width: u32, height: u32) -> Field {
+    pub fn new(width: u32, height: u32, seed: u64) -> Field {
+        return Field::with_shape(width, height, seed, Topology::Rectangle, Tiling::Square);
+    }
+
+    /// Поле с заданной склейкой краёв и формой клеток. Размеры проверяет `Tiling::check_size`.
+    pub fn with_shape(width: u32, height: u32, seed: u64, topology: Topology, tiling: Tiling) -> Field {
         return Field {
             width,
             height,
-            tiles: (0..width*height).map(|_| Tile::new()).collect()
+            tiles: (0..width*height).map(|_| Tile::new()).collect(),
+            topology,
+            tiling,
+            group_sizes: DEFAULT_GROUP_SIZES,
+            group_mines: 1,
+            entangled: 0.0,
+            entanglement: Vec::new(),
+            group_tiles: Vec::new(),
+            probed: false,
+            outcomes: Vec::new(),
+            journal: Journal::default(),
+            rng: Rng::with_seed(seed)
         };
     }
-}
\ No newline at end of file
+
+    /// Клетка для изменения. Пока идёт запись журнала, её прежний вид попадает в журнал.
+    /// Все изменения клеток поля идут через этот метод.
+    pub fn tile_mut(&mut self, index : usize) -> &mut Tile {
+        if let Some(journal) = self.journal.0.as_mut() {
+            journal.push((index, self.tiles[index].clone()));
+        }
+        return &mut self.tiles[index];
+    }
+
+    /// Возвращает все клетки к начальному виду перед новой генерацией.
+    pub fn reset_tiles(&mut self) {
+        for i in 0..self.tiles.len() {
+            if self.tiles[i] != Tile::new() {
+                *self.tile_mut(i) = Tile::new();
+            }
+        }
+    }
+
+    /// Начинает запись клеток, которые меняет действие (см. `take_journal`).
+    pub fn start_journal(&mut self) {
+        self.journal = Journal(Some(Vec::new()));
+    }
+
+    /// Останавливает запись и отдаёт тронутые клетки в виде до действия:
+    /// каждая клетка один раз, по возрастанию индекса. Без записи - пустой список.
+    pub fn take_journal(&mut self) -> Vec<(usize, Tile)> {
+        let mut touched = self.journal.0.take().unwrap_or_default();
+        // сортировка устойчивая, поэтому остаётся самый ранний вид клетки
+        touched.sort_by_key(|(i, _)| *i);
+        touched.dedup_by_key(|(i, _)| *i);
+        return touched;
+    }
+
+    /// Пересобирает `group_tiles` по номерам групп в клетках (после загрузки сохранения).
+    pub fn index_groups(&mut self) {
+        let mut group_tiles : Vec<Vec<usize>> = Vec::new();
+        for (i, tile) in self.tiles.iter().enumerate() {
+            let Some(group) = tile.mine_id.map(|id| id.index()) else { continue; };
+            if group_tiles.len() <= group {
+                group_tiles.resize(group + 1, Vec::new());
+            }
+            group_tiles[group].push(i);
+        }
+        self.group_tiles = group_tiles;
+    }
+
+    /// Индексы клеток группы по возрастанию без просмотра всего поля.
+    pub fn group_indices(&self, group : GroupId) -> Vec<usize> {
+        let Some(tiles) = self.group_tiles.get(group.index()) else { return Vec::new(); };
+        return tiles.iter().copied().filter(|&i| self.tiles[i].mine_id == Some(group)).collect();
+    }
+
+    /// Группы, запутанные с данной, и вид связи.
+    pub fn partners(&self, group : GroupId) -> Vec<(GroupId, Entanglement)> {
+        return self.entanglement.iter().filter_map(|&(a, b, kind)| {
+            if a == group { return Some((b, kind)); }
+            if b == group { return Some((a, kind)); }
+            return None;
+        }).collect();
+    }
+}
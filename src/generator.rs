@@ -1,17 +1,69 @@
-use fastrand;
+use std::ops::RangeInclusive;
 
-use crate::field::Field;
+use fastrand::Rng;
+
+use crate::analysis::SIMULATION_TILE_LIMIT;
+use crate::collapser::Collapser;
+use crate::error::EngineError;
+use crate::field::{Entanglement, Field};
 use crate::misc::MiscMethods;
-use crate::tile::Prob;
+use crate::solver::simulate;
+use crate::tile::{GroupId, Prob};
+
+/// Сколько шагов симуляции (см. `simulate`) генерация без угадывания тратит на все попытки вместе.
+pub const NO_GUESS_STEPS : usize = 2048;
+
+/// Наибольшее число клеток-кандидатов в одной группе, которое вообще поддерживает движок.
+/// От него зависит общий знаменатель вероятностей (`Prob::DENOMINATOR`).
+pub const MAX_GROUP_SIZE : usize = 8;
+
+/// Размеры групп по умолчанию.
+pub const DEFAULT_GROUP_SIZES : RangeInclusive<usize> = 1..=4;
+
+/// Вероятность мины у каждой клетки группы данного размера с `mines` минами: `mines / size`.
+pub fn group_prob(size : usize, mines : usize) -> Option<Prob> {
+    if !(1..=MAX_GROUP_SIZE).contains(&size) || !(1..=size).contains(&mines) {
+        return None;
+    }
+    return Some(Prob(Prob::DENOMINATOR * mines as u32 / size as u32));
+}
+
+/// Все вероятности, которые может иметь клетка в группе допустимого размера.
+pub fn group_probs(sizes : &RangeInclusive<usize>, mines : usize) -> Vec<Prob> {
+    return sizes.clone().filter_map(|size| group_prob(size, mines)).collect();
+}
+
+/// Все доли мин, до которых группы могут сжаться после слабых измерений:
+/// `k / n` для любого `n` до наибольшего размера и `k` до числа мин в группе.
+pub fn probed_group_probs(sizes : &RangeInclusive<usize>, mines : usize) -> Vec<Prob> {
+    let mut result : Vec<Prob> = Vec::new();
+    for size in 1..=*sizes.end() {
+        for k in 1..=mines.min(size) {
+            if let Some(prob) = group_prob(size, k) {
+                if !result.contains(&prob) { result.push(prob); }
+            }
+        }
+    }
+    return result;
+}
+
+/// Сколько групп и кандидатов получит поле из `total_tiles` клеток (первый клик в них не входит).
+pub fn group_counts(total_tiles : usize, group_percent : f64, supertile_percent : f64) -> (usize, usize) {
+    let total_groups = ((total_tiles - 1) as f64 * group_percent).round().max(1.0) as usize;
+    let total_candidates = ((total_tiles - 1) as f64 * supertile_percent).round().max(1.0) as usize;
+    return (total_groups, total_candidates);
+}
 
-fn make_groups(available_tiles : &mut Vec<usize>, total_groups : usize) -> Result<Vec<Vec<usize>>, String> {
+// каждая группа сразу получает `min_size` клеток
+fn make_groups(available_tiles : &mut Vec<usize>, total_groups : usize, min_size : usize) -> Result<Vec<Vec<usize>>, EngineError> {
     let mut groups : Vec<Vec<usize>> = (0..total_groups).map(|_| Vec::new()).collect();
-    if available_tiles.len() < total_groups {
-        return Err(format!("Group count more than tiles ({}), max: {}", total_groups, available_tiles.len()));
+    let needed = total_groups * min_size;
+    if available_tiles.len() < needed {
+        return Err(EngineError::InvalidConfig(format!("groups need {} tiles, max: {}", needed, available_tiles.len())));
     }
 
-    for (group_id, id) in available_tiles.drain(0..total_groups).enumerate() {
-        if let Some(group) = groups.get_mut(group_id) {
+    for (i, id) in available_tiles.drain(0..needed).enumerate() {
+        if let Some(group) = groups.get_mut(i % total_groups) {
             group.push(id);
         }
     }
@@ -19,66 +71,191 @@ fn make_groups(available_tiles : &mut Vec<usize>, total_groups : usize) -> Resul
     return Ok(groups);
 }
 
-fn distribute_tiles(available_tiles: &Vec<usize>, groups: &mut Vec<Vec<usize>>, available_candidates: usize) {
+// объединяет долю `share` групп в случайные пары; без запутанности генератор случайных чисел не трогается
+fn entangle_groups(rng : &mut Rng, total_groups : usize, share : f64) -> Vec<(GroupId, GroupId, Entanglement)> {
+    let pairs = (total_groups as f64 * share / 2.0).floor() as usize;
+    if pairs == 0 { return Vec::new(); }
+    let mut ids : Vec<GroupId> = (0..total_groups).filter_map(GroupId::new).collect();
+    rng.shuffle(&mut ids);
+    return ids.chunks_exact(2).take(pairs)
+        .map(|pair| (pair[0], pair[1], if rng.bool() { Entanglement::Mirror } else { Entanglement::Reverse }))
+        .collect();
+}
+
+// дерево Фенвика над группами, в которых ещё есть место: k-я такая группа ищется за O(log n)
+struct OpenGroups {
+    tree : Vec<usize>,
+    count : usize
+}
+
+impl OpenGroups {
+    fn new(open : &[bool]) -> OpenGroups {
+        let mut tree = vec![0; open.len() + 1];
+        for (i, &is_open) in open.iter().enumerate() {
+            let node = i + 1;
+            tree[node] += is_open as usize;
+            let parent = node + (node & node.wrapping_neg());
+            if parent < tree.len() { tree[parent] += tree[node]; }
+        }
+        return OpenGroups { tree, count: open.iter().filter(|&&is_open| is_open).count() };
+    }
+
+    fn remove(&mut self, group : usize) {
+        let mut node = group + 1;
+        while node < self.tree.len() {
+            self.tree[node] -= 1;
+            node += node & node.wrapping_neg();
+        }
+        self.count -= 1;
+    }
+
+    // номер k-й (с нуля) открытой группы по возрастанию
+    fn nth(&self, k : usize) -> usize {
+        let (mut node, mut rest) = (0, k + 1);
+        let mut step = (self.tree.len() - 1).checked_next_power_of_two().unwrap_or(0);
+        while step > 0 {
+            if node + step < self.tree.len() && self.tree[node + step] < rest {
+                node += step;
+                rest -= self.tree[node];
+            }
+            step /= 2;
+        }
+        return node;
+    }
+}
+
+// каждый кандидат уходит в случайную группу, где ещё есть место (как `rng.choice` по списку таких групп)
+fn distribute_tiles(rng: &mut Rng, available_tiles: &[usize], groups: &mut [Vec<usize>], available_candidates: usize, max_size: usize) {
+    let open : Vec<bool> = groups.iter().map(|g| g.len() < max_size).collect();
+    let mut open_groups = OpenGroups::new(&open);
     for id_tile in available_tiles.iter().take(available_candidates) {
-        let candidate_indices: Vec<usize> = groups
-            .iter()
-            .enumerate()
-            .filter(|(_, g)| g.len() < 4)
-            .map(|(i, _)| i)
-            .collect();
-    
-        if !candidate_indices.is_empty() {
-            let group_idx = fastrand::choice(candidate_indices).unwrap();
-            groups[group_idx].push(*id_tile);
+        if open_groups.count == 0 { break; }
+        let group_idx = open_groups.nth(rng.usize(0..open_groups.count));
+        groups[group_idx].push(*id_tile);
+        if groups[group_idx].len() >= max_size {
+            open_groups.remove(group_idx);
         }
     }
 }
 
 pub trait Generator {
-    fn generate(&mut self, first_click_x: i32, first_click_y: i32, group_percent: f64, supertile_percent: f64) -> Result<(), String>;
+    fn generate(&mut self, first_click_x: i32, first_click_y: i32, group_percent: f64, supertile_percent: f64) -> Result<(), EngineError>;
+    /// Генерирует поле, которое проходится с первого клика одними выводами и измерениями
+    /// на `quant_flags` квантовых флажков. Исходы всех измерений фиксируются при генерации
+    /// (`Field::outcomes`), поэтому проверяется ровно тот исход, который увидит игрок.
+    /// Перегенерирует не больше `attempts` раз и не дольше `NO_GUESS_STEPS` шагов симуляции;
+    /// поля больше `SIMULATION_TILE_LIMIT` клеток не поддерживаются.
+    fn generate_no_guess(&mut self, first_click_x: i32, first_click_y: i32, group_percent: f64, supertile_percent: f64,
+        quant_flags: usize, attempts: usize) -> Result<(), EngineError>;
 
-    fn get_available_tiles(&self, total_tiles : usize, fcx : i32, fcy : i32) -> Vec<usize>;
-    fn set_probabilites(&mut self, groups : &Vec<Vec<usize>>) -> Result<(), String>;
+    fn get_available_tiles(&self, total_tiles : usize, fcx : i32, fcy : i32) -> Result<Vec<usize>, EngineError>;
+    fn set_probabilites(&mut self, groups : &[Vec<usize>]) -> Result<(), EngineError>;
+    fn fix_outcomes(&mut self) -> Result<(), EngineError>;
 }
 
 impl Generator for Field {
-    fn generate(&mut self, first_click_x: i32, first_click_y: i32, group_percent: f64, supertile_percent: f64) -> Result<(), String> {
+    fn generate(&mut self, first_click_x: i32, first_click_y: i32, group_percent: f64, supertile_percent: f64) -> Result<(), EngineError> {
         let total_tiles = (self.width * self.height) as usize;
-        let total_groups = ((total_tiles - 1) as f64 * group_percent).round().max(1.0) as usize;
-        let total_candidates = ((total_tiles - 1) as f64 * supertile_percent).round().max(1.0) as usize;
+        let (total_groups, total_candidates) = group_counts(total_tiles, group_percent, supertile_percent);
         
-        let mut available_tiles = self.get_available_tiles(total_tiles, first_click_x, first_click_y);
-        fastrand::shuffle(&mut available_tiles);
+        let mut available_tiles = self.get_available_tiles(total_tiles, first_click_x, first_click_y)?;
+        self.rng.shuffle(&mut available_tiles);
 
-        let mut groups = make_groups(&mut available_tiles, total_groups)?;
-        distribute_tiles(&available_tiles, &mut groups, total_candidates - total_groups);
+        let (min_size, max_size) = (*self.group_sizes.start(), *self.group_sizes.end());
+        let mut groups = make_groups(&mut available_tiles, total_groups, min_size)?;
+        let extra_candidates = total_candidates.checked_sub(total_groups * min_size).ok_or(EngineError::InvalidConfig(
+            format!("candidates ({}) fewer than groups ({}) of {} tiles", total_candidates, total_groups, min_size)))?;
+        distribute_tiles(&mut self.rng, &available_tiles, &mut groups, extra_candidates, max_size);
         self.set_probabilites(&groups)?;
+        self.entanglement = entangle_groups(&mut self.rng, groups.len(), self.entangled);
 
         return Ok(());
     }
 
-    fn get_available_tiles(&self, total_tiles : usize, fcx : i32, fcy : i32) -> Vec<usize> {
-        return (0..total_tiles).filter(|&i| i != self.coords_to_index(fcx, fcy).unwrap()).collect();
+    fn generate_no_guess(&mut self, first_click_x: i32, first_click_y: i32, group_percent: f64, supertile_percent: f64,
+        quant_flags: usize, attempts: usize) -> Result<(), EngineError> {
+        if self.tiles.len() > SIMULATION_TILE_LIMIT {
+            return Err(EngineError::InvalidConfig(format!("no-guess mode supports up to {} tiles, got {}", SIMULATION_TILE_LIMIT, self.tiles.len())));
+        }
+        let mut steps = NO_GUESS_STEPS;
+        for _ in 0..attempts {
+            self.reset_tiles();
+            self.outcomes.clear();
+            self.generate(first_click_x, first_click_y, group_percent, supertile_percent)?;
+            self.fix_outcomes()?;
+            // исходы зафиксированы, поэтому сид симуляции ни на что не влияет
+            let Some(run) = simulate(self, first_click_x, first_click_y, quant_flags, 0, false, steps) else { break; };
+            if run.solved {
+                return Ok(());
+            }
+            steps -= run.steps;
+        }
+        self.reset_tiles();
+        self.entanglement.clear();
+        self.group_tiles.clear();
+        self.outcomes.clear();
+        return Err(EngineError::GenerationFailed { attempts });
     }
 
-    fn set_probabilites(&mut self, groups : &Vec<Vec<usize>>) -> Result<(), String> {
-        for (mine_id, group) in groups.iter().enumerate() {
-            let prob = match group.len() {
-                1 => Prob(12),
-                2 => Prob(6),
-                3 => Prob(4),
-                4 => Prob(3),
-                _ => return Err(String::from("Invalid group size detected")),
-            }; // скорее всего баг появлялся где-то здесь, и чтобы его исключить я сделал это
+    // измеряет все группы на копии поля (запутанные пары - вместе) и запоминает, где оказались мины
+    fn fix_outcomes(&mut self) -> Result<(), EngineError> {
+        let mut measured = self.clone();
+        measured.rng.seed(self.rng.u64(..));
+        for group in 0..self.group_tiles.len() {
+            let Some(group) = GroupId::new(group) else { break; };
+            match measured.collapse_group(group) {
+                Ok(()) | Err(EngineError::GroupCollapsed(_)) => {},
+                Err(e) => return Err(e)
+            }
+        }
+        self.outcomes = measured.tiles.iter().map(|tile| tile.prob == Prob::MINE).collect();
+        return Ok(());
+    }
+
+    // все клетки, кроме первого клика
+    fn get_available_tiles(&self, total_tiles : usize, fcx : i32, fcy : i32) -> Result<Vec<usize>, EngineError> {
+        let first = self.coords_to_index(fcx, fcy).ok_or(EngineError::OutOfBounds { x: fcx, y: fcy })?;
+        return Ok((0..total_tiles).filter(|&i| i != first).collect());
+    }
+
+    fn set_probabilites(&mut self, groups : &[Vec<usize>]) -> Result<(), EngineError> {
+        for (index, group) in groups.iter().enumerate() {
+            let mine_id = group_id(index, groups.len())?;
+            let prob = group_prob(group.len(), self.group_mines).ok_or(EngineError::InvalidConfig(format!("invalid group size ({})", group.len())))?; // скорее всего баг появлялся где-то здесь, и чтобы его исключить я сделал это
 
             for id in group {
-                if let Some(tile) = self.tiles.get_mut(*id) {
+                if *id < self.tiles.len() {
+                    let tile = self.tile_mut(*id);
                     tile.prob = prob.clone();
-                    tile.mine_id = mine_id as i16;
+                    tile.mine_id = Some(mine_id);
                 }
             }
         }
+        self.group_tiles = groups.iter().map(|group| {
+            let mut tiles = group.clone();
+            tiles.sort_unstable();
+            return tiles;
+        }).collect();
         return Ok(());
     }
 }
+
+// номер группы `index` из `count`; ошибка конфига, если номеров не хватает
+fn group_id(index : usize, count : usize) -> Result<GroupId, EngineError> {
+    return GroupId::new(index).ok_or(EngineError::InvalidConfig(
+        format!("too many groups ({}), max: {}", count, GroupId::MAX as u64 + 1)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_id_overflow_is_config_error() {
+        let last = GroupId::MAX as usize;
+        assert_eq!(group_id(last, last + 1), Ok(GroupId(GroupId::MAX)));
+        let error = group_id(last + 1, last + 2).unwrap_err();
+        assert_eq!(error.code(), "invalidConfig");
+        assert!(error.to_string().contains("too many groups"));
+    }
+}
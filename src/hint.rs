@@ -0,0 +1,73 @@
+use crate::field::Field;
+use crate::odds::Odds;
+use crate::solver::{index_to_coords, DeductionKind, Solver};
+use crate::tile::*;
+
+/// Что подсказка предлагает сделать.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HintAction {
+    Open,      // лопата
+    Flag,      // обычный флажок
+    QuantFlag, // квантовый флажок, чтобы измерить группу
+    Collapse   // измерить уже поставленные квантовые флажки
+}
+
+/// Почему подсказка предлагает этот ход.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HintReason {
+    FirstClick,                               // первый клик всегда безопасен
+    ForcedSafe { neighbours : Vec<(i32, i32)> }, // следует из чисел соседей
+    ForcedMine { neighbours : Vec<(i32, i32)> },
+    Measure { neighbours : Vec<(i32, i32)> },    // клетка точно в группе, измерение даст новые числа
+    PendingQuantFlags,                        // квантовые флажки стоят, но ещё не измерены
+    LowestRisk { odds : f64 }                 // выводов нет, у клетки самая низкая вероятность мины (см. `Odds`)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hint {
+    pub x : i32,
+    pub y : i32,
+    pub action : HintAction,
+    pub reason : HintReason
+}
+
+pub trait Hinter {
+    /// Лучший ход по тому, что видит игрок: сначала точные выводы, потом измерения
+    /// (если `can_measure`), потом клетка с наименьшей вероятностью мины.
+    /// `groups` и `candidates` нужны для оценки вероятностей (см. `Odds`).
+    fn hint(&self, groups : usize, candidates : usize, can_measure : bool) -> Option<Hint>;
+}
+
+impl Hinter for Field {
+    fn hint(&self, groups : usize, candidates : usize, can_measure : bool) -> Option<Hint> {
+        let untouched = |x : i32, y : i32| self.tiles[(y as u32 * self.width + x as u32) as usize].status == TileStatus::None;
+        let deductions = self.deductions();
+
+        if let Some(d) = deductions.iter().find(|d| d.kind == DeductionKind::Safe && untouched(d.x, d.y)) {
+            return Some(Hint { x: d.x, y: d.y, action: HintAction::Open, reason: HintReason::ForcedSafe { neighbours: d.reasons.clone() } });
+        }
+        if let Some(d) = deductions.iter().find(|d| d.kind == DeductionKind::Mine && untouched(d.x, d.y)) {
+            return Some(Hint { x: d.x, y: d.y, action: HintAction::Flag, reason: HintReason::ForcedMine { neighbours: d.reasons.clone() } });
+        }
+        if let Some(index) = self.tiles.iter().position(|tile| tile.status == TileStatus::QuantFlag) {
+            let (x, y) = index_to_coords(self, index);
+            return Some(Hint { x, y, action: HintAction::Collapse, reason: HintReason::PendingQuantFlags });
+        }
+        if can_measure {
+            // выводы строятся по видимому, но флажок на уже схлопнутую группу подсказка не предлагает: он пропал бы впустую
+            let candidate = deductions.iter().find(|d| matches!(d.kind, DeductionKind::Candidate(_))
+                && untouched(d.x, d.y) && !self.tiles[(d.y as u32 * self.width + d.x as u32) as usize].collapsed);
+            if let Some(d) = candidate {
+                return Some(Hint { x: d.x, y: d.y, action: HintAction::QuantFlag, reason: HintReason::Measure { neighbours: d.reasons.clone() } });
+            }
+        }
+
+        let odds = self.mine_odds(groups, candidates);
+        let (index, risk) = odds.iter().enumerate()
+            .filter(|(i, _)| self.tiles[*i].status == TileStatus::None)
+            .filter_map(|(i, odds)| odds.map(|odds| (i, odds)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+        let (x, y) = index_to_coords(self, index);
+        return Some(Hint { x, y, action: HintAction::Open, reason: HintReason::LowestRisk { odds: risk } });
+    }
+}
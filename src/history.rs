@@ -0,0 +1,111 @@
+use crate::analysis::BoardAnalysis;
+use crate::engine::GameState;
+use crate::field::{Entanglement, Field};
+use crate::tile::{GroupId, Tile};
+
+/// Часть состояния движка, которую меняет одно действие помимо клеток.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineState {
+    pub flag_count : usize,
+    pub probe_count : usize,
+    pub state : GameState,
+    pub probed : bool, // `Field::probed`
+    pub rng_seed : u64
+}
+
+/// Что первый клик создаёт помимо клеток: скрытые группы поля, разбор и время начала партии.
+/// До первого клика всё это пусто (`Generation::default`).
+#[derive(Debug, Clone, Default)]
+pub struct Generation {
+    pub entanglement : Vec<(GroupId, GroupId, Entanglement)>,
+    pub group_tiles : Vec<Vec<usize>>,
+    pub outcomes : Vec<bool>,
+    pub analysis : Option<BoardAnalysis>,
+    pub started_at : Option<f64>
+}
+
+/// Одно действие игрока: клетки до и после, и состояние движка до и после.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    tiles : Vec<(usize, Tile, Tile)>, // (индекс, до, после)
+    before : EngineState,
+    after : EngineState,
+    generation : Option<Box<Generation>> // только у первого клика
+}
+
+impl HistoryEntry {
+    /// Сравнивает клетки, тронутые действием (`Field::take_journal`), с полем после него.
+    /// `None`, если ничего не поменялось.
+    pub fn diff(touched : &[(usize, Tile)], before : EngineState, field : &Field, after : EngineState,
+        generation : Option<Generation>) -> Option<HistoryEntry> {
+        let tiles : Vec<(usize, Tile, Tile)> = touched.iter()
+            .filter(|(i, old)| *old != field.tiles[*i])
+            .map(|(i, old)| (*i, old.clone(), field.tiles[*i].clone()))
+            .collect();
+        if tiles.is_empty() && before == after && generation.is_none() {
+            return None;
+        }
+        return Some(HistoryEntry { tiles, before, after, generation: generation.map(Box::new) });
+    }
+
+    // отмена первого клика возвращает пустую генерацию, повтор - сохранённую
+    fn apply(&self, field : &mut Field, undo : bool) -> (EngineState, Option<Generation>) {
+        for (i, before, after) in &self.tiles {
+            if *i < field.tiles.len() {
+                *field.tile_mut(*i) = if undo { before.clone() } else { after.clone() };
+            }
+        }
+        let state = if undo { &self.before } else { &self.after };
+        field.rng.seed(state.rng_seed);
+        let generation = self.generation.as_ref().map(|generation| if undo { Generation::default() } else { (**generation).clone() });
+        return (state.clone(), generation);
+    }
+}
+
+/// Стеки отмены и повтора. Новое действие очищает стек повтора.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo : Vec<HistoryEntry>,
+    redo : Vec<HistoryEntry>
+}
+
+impl History {
+    pub fn new() -> History {
+        return History { undo: Vec::new(), redo: Vec::new() };
+    }
+
+    pub fn push(&mut self, entry : HistoryEntry) {
+        self.undo.push(entry);
+        self.redo.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        return !self.undo.is_empty();
+    }
+
+    pub fn can_redo(&self) -> bool {
+        return !self.redo.is_empty();
+    }
+
+    /// Откатывает последнее действие. Возвращает восстановленное состояние и, если действие
+    /// было первым кликом, генерацию, которую нужно вернуть движку.
+    pub fn undo(&mut self, field : &mut Field) -> Option<(EngineState, Option<Generation>)> {
+        let entry = self.undo.pop()?;
+        let state = entry.apply(field, true);
+        self.redo.push(entry);
+        return Some(state);
+    }
+
+    /// Повторяет последнее отменённое действие.
+    pub fn redo(&mut self, field : &mut Field) -> Option<(EngineState, Option<Generation>)> {
+        let entry = self.redo.pop()?;
+        let state = entry.apply(field, false);
+        self.undo.push(entry);
+        return Some(state);
+    }
+}
@@ -1,14 +1,43 @@
+#![allow(clippy::needless_return)]
 
 extern crate alloc;
 
-mod tile;
-mod engine;
+pub mod tile;
+pub mod engine;
+pub mod error;
+pub mod hint;
+pub mod history;
+pub mod summary;
+#[cfg(feature = "wasm")]
+mod wasm;
 
 // field modules
-mod field;
-mod bytes;
-mod collapser;
-mod generator;
-mod misc;
-mod opener;
-//mod new_generator;
\ No newline at end of file
+pub mod field;
+pub mod analysis;
+pub mod bytes;
+pub mod changes;
+pub mod collapser;
+pub mod generator;
+pub mod misc;
+pub mod odds;
+pub mod opener;
+pub mod replay;
+pub mod solver;
+//mod new_generator;
+
+pub use analysis::{Analyzer, BoardAnalysis, Difficulty};
+pub use bytes::Byter;
+pub use changes::TileChange;
+pub use collapser::Collapser;
+pub use engine::{ConfigCounts, GameConfig, GameConfigBuilder, GameEngine, GameState, ToolType};
+pub use error::EngineError;
+pub use field::{Entanglement, Field, Partner, TileShape, Tiling, Topology};
+pub use generator::Generator;
+pub use hint::{Hint, HintAction, HintReason, Hinter};
+pub use misc::MiscMethods;
+pub use odds::Odds;
+pub use opener::{Chord, TileOpener};
+pub use replay::{Move, Replay, ReplayPlayer};
+pub use solver::{Deduction, DeductionKind, Solver};
+pub use summary::{GameStats, GameSummary};
+pub use tile::{GroupId, Prob, Tile, TileStatus, TileView};
@@ -1,13 +1,15 @@
-use crate::field::{Field, DIRECTIONS};
+use crate::field::Field;
+use crate::error::EngineError;
 use crate::tile::*;
 
 pub trait MiscMethods {
     fn is_inside_bounds(&self, x : i32, y : i32) -> bool;
+    fn neighbours(&self, x : i32, y : i32) -> Vec<(i32, i32)>;
     fn get_tile(&self, x: i32, y: i32) -> Option<&Tile>;
     fn get_mut_tile(&mut self, x: i32, y: i32) -> Option<&mut Tile>;
     fn coords_to_index(&self, x: i32, y: i32) -> Option<usize>;
-    fn get_group_elements(&self, group_id : i16) -> Vec<(i32, i32)>;
-    fn around_prob_sum(&self, x : i32, y : i32) -> Result<Prob, String>;
+    fn get_group_elements(&self, group_id : GroupId) -> Vec<(i32, i32)>;
+    fn around_prob_sum(&self, x : i32, y : i32) -> Result<Prob, EngineError>;
     fn set_tile_status(&mut self, x: i32, y: i32, status: TileStatus);
     fn is_win(&self) -> bool;
 }
@@ -17,6 +19,11 @@ impl MiscMethods for Field {
         return 0 <= x && 0 <= y && x < self.width as i32 && y < self.height as i32;
     }
 
+    /// Соседи клетки с учётом формы клеток и склейки краёв поля (см. `Topology::neighbours`).
+    fn neighbours(&self, x : i32, y : i32) -> Vec<(i32, i32)> {
+        return self.topology.neighbours(self.tiling.directions(x, y), x, y, self.width, self.height);
+    }
+
     fn get_tile(&self, x: i32, y: i32) -> Option<&Tile> {
         let index = self.coords_to_index(x, y)?;
         return self.tiles.get(index);
@@ -24,7 +31,7 @@ impl MiscMethods for Field {
 
     fn get_mut_tile(&mut self, x: i32, y: i32) -> Option<&mut Tile> {
         let index = self.coords_to_index(x, y)?;
-        return self.tiles.get_mut(index);
+        return Some(self.tile_mut(index));
     }
 
     fn coords_to_index(&self, x: i32, y: i32) -> Option<usize> {
@@ -34,28 +41,17 @@ impl MiscMethods for Field {
         return None;
     }
 
-    fn get_group_elements(&self, mine_id : i16) -> Vec<(i32, i32)> {
-        let mut group = Vec::new();
-        let (width, height) = (self.width, self.height);
-        for y in 0..height as i32 {
-            for x in 0..width as i32 {
-                if let Some(tile) = self.get_tile(x as i32, y as i32) {
-                    if tile.mine_id == mine_id {
-                        group.push((x, y));
-                    }
-                }
-            }
-        }
-
-        return group;
+    fn get_group_elements(&self, mine_id : GroupId) -> Vec<(i32, i32)> {
+        let width = self.width as usize;
+        return self.group_indices(mine_id).into_iter().map(|i| ((i % width) as i32, (i / width) as i32)).collect();
     }
 
-    fn around_prob_sum(&self, x : i32, y : i32) -> Result<Prob, String> {
-        if !self.is_inside_bounds(x, y) {return Err(format!("Tile ({}, {}) outside the bounds", x, y));}
-        let mut result = Prob(0);
+    fn around_prob_sum(&self, x : i32, y : i32) -> Result<Prob, EngineError> {
+        if !self.is_inside_bounds(x, y) {return Err(EngineError::OutOfBounds { x, y });}
+        let mut result = Prob::ZERO;
 
-        for (dx, dy) in DIRECTIONS {
-            if let Some(tile) = self.get_tile(x + dx, y + dy) {
+        for (nx, ny) in self.neighbours(x, y) {
+            if let Some(tile) = self.get_tile(nx, ny) {
                 result.add(&tile.prob);
             }
         }
@@ -72,8 +68,8 @@ impl MiscMethods for Field {
     fn is_win(&self) -> bool {
         self.tiles.iter().all(|tile| {
             match tile.prob {
-                Prob(0) => tile.status == TileStatus::Opened,
-                Prob(12) => tile.status == TileStatus::Flag,
+                Prob::ZERO => tile.status == TileStatus::Opened,
+                Prob::MINE => tile.status == TileStatus::Flag,
                 _ => false,
             }
         })
@@ -0,0 +1,549 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+
+use fastrand::Rng;
+
+use crate::field::Field;
+use crate::generator::{group_prob, MAX_GROUP_SIZE};
+use crate::solver::{Problem, SEARCH_BUDGET};
+use crate::tile::*;
+
+// сколько раз прогоняется раздача кандидатов, чтобы оценить распределение размеров групп
+const SIZE_SAMPLES : usize = 64;
+
+// поля больше этого числа клеток считаются приближённо (см. `Odds::mine_odds`)
+const EXACT_TILE_LIMIT : usize = 1024;
+
+// сколько состояний допускается при подсчёте решений одной компоненты; если больше, поле считается приближённо
+const STATE_LIMIT : usize = 1 << 16;
+
+// сколько клеток допускается в таблицах раскладов групп; на полях с большим числом групп и кандидатов
+// подсчёт приближённый
+const CELL_LIMIT : usize = 1 << 20;
+
+// во сколько раз (ln) расклад групп может быть менее вероятен самого вероятного, чтобы его ещё учитывать
+const NEGLIGIBLE : f64 = 50.0;
+
+// сколько пар (вариант компоненты, вариант остальных компонент) допускается при точном сложении
+const JOINT_LIMIT : usize = 1 << 16;
+
+// сколько совместных вариантов берётся, если точное сложение не уложилось в `JOINT_LIMIT`
+const JOINT_SAMPLES : usize = 1024;
+
+/// Распределение размеров групп при раздаче `candidates` кандидатов по `groups` группам так же,
+/// как это делает генератор: каждая группа начинается с минимального числа клеток, остальные клетки
+/// по одной уходят в случайную неполную группу. `result[s]` - доля групп размера `s`.
+pub fn group_size_distribution(groups : usize, candidates : usize, group_sizes : &RangeInclusive<usize>) -> Vec<f64> {
+    let (min_size, max_size) = (*group_sizes.start(), *group_sizes.end());
+    let mut result = vec![0.0; MAX_GROUP_SIZE + 1];
+    if groups == 0 { return result; }
+    let mut rng = Rng::with_seed(0); // фиксированный сид: оценка не должна плавать между вызовами
+    let extra = candidates.saturating_sub(groups * min_size);
+    for _ in 0..SIZE_SAMPLES {
+        let mut sizes = vec![min_size; groups];
+        let mut open : Vec<usize> = (0..groups).collect();
+        for _ in 0..extra {
+            if open.is_empty() { break; }
+            let slot = rng.usize(0..open.len());
+            sizes[open[slot]] += 1;
+            if sizes[open[slot]] >= max_size {
+                open.swap_remove(slot);
+            }
+        }
+        for size in sizes {
+            result[size] += 1.0;
+        }
+    }
+    let total = (groups * SIZE_SAMPLES) as f64;
+    result.iter_mut().for_each(|share| *share /= total);
+    return result;
+}
+
+/// Априорные веса значений клетки (в порядке `values`) для приближённого подсчёта:
+/// пропорциональны ожидаемому числу клеток с такой вероятностью на неизмеренном поле.
+fn value_prior(values : &[u32], field : &Field, groups : usize, candidates : usize) -> Vec<f64> {
+    let sizes = group_size_distribution(groups, candidates, &field.group_sizes);
+    let mut prior = vec![0.0; values.len()];
+    prior[0] = (field.tiles.len().saturating_sub(1)).saturating_sub(candidates) as f64;
+    for (size, share) in sizes.iter().enumerate() {
+        let Some(prob) = group_prob(size, field.group_mines) else { continue; };
+        if let Some(i) = values.iter().position(|v| *v == prob.0) {
+            prior[i] += groups as f64 * share * size as f64;
+        }
+    }
+    return prior;
+}
+
+// ln(e^a + e^b) без переполнения
+fn log_add(a : f64, b : f64) -> f64 {
+    let (hi, lo) = if a > b { (a, b) } else { (b, a) };
+    if lo == f64::NEG_INFINITY { return hi; }
+    return hi + (lo - hi).exp().ln_1p();
+}
+
+// чем может быть закрытая клетка с данным значением
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Empty,       // вне групп или пустая клетка измеренной группы
+    Mine,        // мина измеренной группы (или группы, где мины все клетки)
+    Block(usize) // клетка неизмеренной группы этого размера
+}
+
+/// Всё поле с точки зрения игрока: закрытые клетки, мины на них (в группе всегда `group_mines` мин,
+/// измерена она или нет) и сколько групп и кандидатов раздал генератор.
+struct Model {
+    kinds : Vec<Kind>,     // по индексам `Problem::values`
+    blocks : Vec<usize>,   // индексы значений неизмеренных групп
+    closed : usize,
+    mines : usize,
+    ln_fact : Vec<f64>,
+    worlds : Vec<World>,
+    weights : HashMap<Vec<u16>, f64>
+}
+
+// сколько на закрытых клетках неизмеренных групп каждого размера (по `Model::blocks`)
+struct World {
+    tiles : Vec<usize>, // клеток неизмеренных групп каждого размера
+    mines : usize,      // клеток с целой миной
+    empties : usize,
+    ln : f64            // ln числа способов разбить клетки на группы без учёта открытых чисел
+}
+
+impl Model {
+    /// `None`, если поле считается приближённо: оно слишком большое или после щупа,
+    /// когда размеры групп по долям уже не восстановить.
+    fn new(field : &Field, values : &[u32], groups : usize, candidates : usize) -> Option<Model> {
+        if field.probed || field.tiles.len() > EXACT_TILE_LIMIT { return None; }
+        let group_mines = field.group_mines;
+        let full = Prob::MINE.0 as usize * group_mines;
+        let kinds = values.iter().enumerate().map(|(i, &value)| match i {
+            0 => Some(Kind::Empty),
+            1 => Some(Kind::Mine),
+            _ => {
+                let size = full / value as usize;
+                (size > group_mines && group_prob(size, group_mines) == Some(Prob(value))).then_some(Kind::Block(size))
+            }
+        }).collect::<Option<Vec<Kind>>>()?;
+        let blocks : Vec<usize> = (0..kinds.len()).filter(|i| matches!(kinds[*i], Kind::Block(_))).collect();
+        let sizes : Vec<usize> = blocks.iter().map(|i| match kinds[*i] { Kind::Block(size) => size, _ => 0 }).collect();
+
+        let closed = field.tiles.iter().filter(|tile| tile.status != TileStatus::Opened).count();
+        let exploded = field.tiles.iter().filter(|tile| tile.status == TileStatus::Opened && tile.prob == Prob::MINE).count();
+        let mines = (groups * group_mines).checked_sub(exploded)?;
+        let candidates = candidates.min(groups * field.group_sizes.end()).min(field.tiles.len().saturating_sub(1));
+        let width = candidates.min(closed) + 1;
+        if (groups + 1) * width * (sizes.len() + 1) > CELL_LIMIT { return None; }
+        let mut ln_fact = vec![0.0; field.tiles.len().max(MAX_GROUP_SIZE) + 1];
+        for n in 1..ln_fact.len() {
+            ln_fact[n] = ln_fact[n - 1] + (n as f64).ln();
+        }
+        let (min_size, max_size) = (*field.group_sizes.start(), *field.group_sizes.end());
+
+        // остальные группы измерены или целиком из мин: их мины на закрытых клетках целые.
+        // `None` - при `used` неизмеренных группах с `tiles` клетками поле не сходится с конфигом
+        let rest = |used : usize, tiles : usize| -> Option<(usize, usize)> {
+            let mines = mines.checked_sub(group_mines * used)?;
+            let (others, rest) = (groups - used, candidates - tiles);
+            if rest < others * min_size || rest > others * max_size { return None; }
+            return Some((mines, closed.checked_sub(tiles + mines)?));
+        };
+        // `best[j][cell]` - наибольший ln веса, которого можно достичь от клетки таблицы, раздав размеры с `j`-го
+        let cell = |used : usize, tiles : usize| used * width + tiles;
+        let mut best = vec![vec![f64::NEG_INFINITY; (groups + 1) * width]; sizes.len() + 1];
+        for used in 0..=groups {
+            for tiles in 0..width {
+                if let Some((mines, empties)) = rest(used, tiles) {
+                    best[sizes.len()][cell(used, tiles)] = -ln_fact[mines] - ln_fact[empties];
+                }
+            }
+        }
+        let term = |size : usize, made : usize| -(made as f64) * ln_fact[size] - ln_fact[made];
+        for (j, &size) in sizes.iter().enumerate().rev() {
+            for used in 0..=groups {
+                for tiles in 0..width {
+                    let mut top = f64::NEG_INFINITY;
+                    let mut made = 0;
+                    while used + made <= groups && tiles + size * made < width {
+                        top = top.max(term(size, made) + best[j + 1][cell(used + made, tiles + size * made)]);
+                        made += 1;
+                    }
+                    best[j][cell(used, tiles)] = top;
+                }
+            }
+        }
+        let top = best[0][0];
+        if top == f64::NEG_INFINITY { return None; }
+
+        // обход в глубину только по раскладам, которые не слишком далеко от самого вероятного
+        let mut worlds = Vec::new();
+        let mut stack = vec![(Vec::new(), 0, 0, 0.0)];
+        while let Some((made, used, tiles, ln)) = stack.pop() {
+            let j = made.len();
+            if j == sizes.len() {
+                let Some((mines, empties)) = rest(used, tiles) else { continue; };
+                let tiles = made.iter().zip(&sizes).map(|(m, s)| m * s).collect();
+                worlds.push(World { tiles, mines, empties, ln: ln - ln_fact[mines] - ln_fact[empties] });
+                continue;
+            }
+            let size = sizes[j];
+            let mut next = 0;
+            while used + next <= groups && tiles + size * next < width {
+                let ln = ln + term(size, next);
+                if ln + best[j + 1][cell(used + next, tiles + size * next)] >= top - NEGLIGIBLE {
+                    let mut made = made.clone();
+                    made.push(next);
+                    stack.push((made, used + next, tiles + size * next, ln));
+                }
+                next += 1;
+            }
+        }
+        return Some(Model { kinds, blocks, closed, mines, ln_fact, worlds, weights: HashMap::new() });
+    }
+
+    /// ln числа раскладов всего поля (с точностью до общего множителя), в которых на закрытых клетках
+    /// у открытых чисел `counts[i]` клеток со значением `values[i]`.
+    fn ln_weight(&mut self, counts : &[u16]) -> f64 {
+        if let Some(weight) = self.weights.get(counts) { return *weight; }
+        let falling = |n : usize, k : u16| -> f64 {
+            let k = k as usize;
+            return if n >= k { self.ln_fact[n] - self.ln_fact[n - k] } else { f64::NEG_INFINITY };
+        };
+        let terms : Vec<f64> = self.worlds.iter().map(|world| {
+            // сколькими способами клетки у чисел выбираются среди всех закрытых клеток своего значения
+            let blocks : f64 = self.blocks.iter().zip(&world.tiles).map(|(i, tiles)| falling(*tiles, counts[*i])).sum();
+            return world.ln + blocks + falling(world.empties, counts[0]) + falling(world.mines, counts[1]);
+        }).collect();
+        let top = terms.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let weight = if top == f64::NEG_INFINITY { top } else { top + terms.iter().map(|t| (t - top).exp()).sum::<f64>().ln() };
+        self.weights.insert(counts.to_vec(), weight);
+        return weight;
+    }
+}
+
+// решения компоненты (или её начала) с одинаковым числом клеток каждого значения
+struct Variant {
+    solutions : f64,
+    mines : Vec<f64> // сумма долей мины каждой переменной по этим решениям
+}
+
+/// Решения компоненты, сложенные по числу клеток каждого значения. Переменные назначаются по порядку,
+/// состояние - остатки её ограничений и число клеток каждого значения среди назначенных, поэтому
+/// решения не перебираются по одному. `None` - состояний больше `STATE_LIMIT`.
+fn variants(problem : &Problem, vars : &[usize]) -> Option<Vec<(Vec<u16>, Variant)>> {
+    let full = Prob::MINE.0 as f64;
+    let mut constraints : Vec<usize> = vars.iter().flat_map(|var| problem.var_constraints[*var].iter().cloned()).collect();
+    constraints.sort_unstable();
+    constraints.dedup();
+    let mut slot = vec![usize::MAX; problem.constraints.len()];
+    constraints.iter().enumerate().for_each(|(i, c)| slot[*c] = i);
+    let max_of = |var : usize| (0..problem.values.len()).filter(|i| problem.domains[var] & (1 << i) != 0).map(|i| problem.values[i]).max().unwrap_or(0);
+    // сколько ещё могут добавить неназначенные переменные каждого ограничения
+    let mut room : Vec<u32> = constraints.iter().map(|c| problem.constraints[*c].vars.iter().map(|var| max_of(*var)).sum()).collect();
+
+    let start = (constraints.iter().map(|c| problem.constraints[*c].target).collect::<Vec<u32>>(), vec![0u16; problem.values.len()]);
+    let mut states = BTreeMap::from([(start, Variant { solutions: 1.0, mines: vec![0.0; vars.len()] })]);
+    for (i, &var) in vars.iter().enumerate() {
+        let own : Vec<usize> = problem.var_constraints[var].iter().map(|c| slot[*c]).collect();
+        own.iter().for_each(|s| room[*s] -= max_of(var));
+        let mut next : BTreeMap<(Vec<u32>, Vec<u16>), Variant> = BTreeMap::new();
+        for ((left, counts), partial) in states {
+            for (value_index, &value) in problem.values.iter().enumerate() {
+                if problem.domains[var] & (1 << value_index) == 0 { continue; }
+                let mut left = left.clone();
+                let mut fits = true;
+                for &s in &own {
+                    // остаток не может уйти в минус или стать больше, чем добавят остальные переменные
+                    fits = fits && left[s] >= value && left[s] - value <= room[s];
+                    left[s] = left[s].saturating_sub(value);
+                }
+                if !fits { continue; }
+                let mut counts = counts.clone();
+                counts[value_index] += 1;
+                let entry = next.entry((left, counts)).or_insert_with(|| Variant { solutions: 0.0, mines: vec![0.0; vars.len()] });
+                entry.solutions += partial.solutions;
+                entry.mines.iter_mut().zip(&partial.mines).for_each(|(a, b)| *a += b);
+                entry.mines[i] += partial.solutions * value as f64 / full;
+            }
+        }
+        if next.len() > STATE_LIMIT { return None; }
+        states = next;
+    }
+    // остатки всех ограничений к концу нулевые, состояния различаются только числом клеток
+    return Some(states.into_iter().map(|((_, counts), variant)| (counts, variant)).collect());
+}
+
+// свёртка: сколько решений у компонент вместе при каждом числе клеток каждого значения
+fn convolve(sums : &BTreeMap<Vec<u16>, f64>, variants : &[(Vec<u16>, f64)]) -> Option<BTreeMap<Vec<u16>, f64>> {
+    if sums.len() * variants.len() > JOINT_LIMIT { return None; }
+    let mut result = BTreeMap::new();
+    for (counts, solutions) in sums {
+        for (other, other_solutions) in variants {
+            let joint : Vec<u16> = counts.iter().zip(other).map(|(a, b)| a + b).collect();
+            *result.entry(joint).or_insert(0.0) += solutions * other_solutions;
+        }
+    }
+    return Some(result);
+}
+
+/// ln веса каждого варианта каждой компоненты с учётом всех остальных компонент. `None` - слишком много сочетаний.
+fn joint_weights(variants : &[Vec<(Vec<u16>, f64)>], model : &mut Model) -> Option<Vec<Vec<f64>>> {
+    let none = BTreeMap::from([(vec![0u16; model.kinds.len()], 1.0)]);
+    let mut prefix = vec![none.clone()];
+    for component in variants {
+        prefix.push(convolve(prefix.last().unwrap(), component)?);
+    }
+    let mut suffix = vec![none; variants.len() + 1];
+    for i in (0..variants.len()).rev() {
+        suffix[i] = convolve(&suffix[i + 1], &variants[i])?;
+    }
+
+    let mut result = Vec::new();
+    for (i, component) in variants.iter().enumerate() {
+        let rest : Vec<(Vec<u16>, f64)> = suffix[i + 1].iter().map(|(counts, solutions)| (counts.clone(), *solutions)).collect();
+        let others : Vec<(Vec<u16>, f64)> = convolve(&prefix[i], &rest)?.into_iter().collect();
+        if others.len() * component.len() > JOINT_LIMIT { return None; }
+        let weights = component.iter().map(|(counts, solutions)| {
+            let mut weight = f64::NEG_INFINITY;
+            for (other, other_solutions) in &others {
+                let joint : Vec<u16> = counts.iter().zip(other).map(|(a, b)| a + b).collect();
+                weight = log_add(weight, other_solutions.ln() + model.ln_weight(&joint));
+            }
+            return solutions.ln() + weight;
+        }).collect();
+        result.push(weights);
+    }
+    return Some(result);
+}
+
+/// То же, что `joint_weights`, выборкой: варианты компонент берутся пропорционально числу решений.
+fn sampled_weights(variants : &[Vec<(Vec<u16>, f64)>], model : &mut Model) -> Vec<Vec<f64>> {
+    let mut rng = Rng::with_seed(0); // фиксированный сид: оценка не должна плавать между вызовами
+    let mut result : Vec<Vec<f64>> = variants.iter().map(|component| vec![f64::NEG_INFINITY; component.len()]).collect();
+    let totals : Vec<f64> = variants.iter().map(|component| component.iter().map(|(_, solutions)| solutions).sum()).collect();
+    for _ in 0..JOINT_SAMPLES {
+        let mut joint = vec![0u16; model.kinds.len()];
+        let mut picks = Vec::with_capacity(variants.len());
+        for (component, total) in variants.iter().zip(&totals) {
+            let mut left = rng.f64() * total;
+            let pick = component.iter().position(|(_, solutions)| { left -= solutions; left < 0.0 }).unwrap_or(component.len() - 1);
+            joint.iter_mut().zip(&component[pick].0).for_each(|(a, b)| *a += b);
+            picks.push(pick);
+        }
+        let weight = model.ln_weight(&joint);
+        for (weights, pick) in result.iter_mut().zip(picks) {
+            weights[pick] = log_add(weights[pick], weight);
+        }
+    }
+    return result;
+}
+
+// точный подсчёт; `None` - компонента слишком сложная или видимое не сходится с моделью
+fn exact_odds(field : &Field, problem : &Problem, model : &mut Model) -> Option<Vec<Option<f64>>> {
+    let mut components = Vec::new();
+    for vars in problem.components() {
+        let variants = variants(problem, &vars)?;
+        components.push((vars, variants));
+    }
+
+    let counts : Vec<Vec<(Vec<u16>, f64)>> = components.iter()
+        .map(|(_, variants)| variants.iter().map(|(counts, variant)| (counts.clone(), variant.solutions)).collect())
+        .collect();
+    let weights = match joint_weights(&counts, model) {
+        Some(weights) => weights,
+        None => sampled_weights(&counts, model)
+    };
+
+    let mut result : Vec<Option<f64>> = vec![None; field.tiles.len()];
+    let mut frontier_mines = 0.0;
+    for ((vars, variants), weights) in components.iter().zip(weights) {
+        let top = weights.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        if top == f64::NEG_INFINITY { return None; }
+        let shares : Vec<f64> = weights.iter().map(|weight| (weight - top).exp()).collect();
+        let total : f64 = shares.iter().sum();
+        for (i, &var) in vars.iter().enumerate() {
+            let odds : f64 = variants.iter().zip(&shares).map(|((_, variant), share)| share / total * variant.mines[i] / variant.solutions).sum();
+            result[problem.tiles[var]] = Some(odds);
+            frontier_mines += odds;
+        }
+    }
+
+    // остальные закрытые клетки ничем не различаются: на них поровну оставшихся мин
+    let sea = model.closed - problem.tiles.len();
+    let sea_odds = if sea > 0 { ((model.mines as f64 - frontier_mines) / sea as f64).clamp(0.0, 1.0) } else { 0.0 };
+    for (index, tile) in field.tiles.iter().enumerate() {
+        if tile.status != TileStatus::Opened && result[index].is_none() {
+            result[index] = Some(sea_odds);
+        }
+    }
+    return Some(result);
+}
+
+// приближённый подсчёт: решения каждой компоненты с весами `value_prior`, клетки независимы
+fn independent_odds(field : &Field, problem : &Problem, groups : usize, candidates : usize, consistent : bool) -> Vec<Option<f64>> {
+    let full = Prob::MINE.0 as f64;
+    let prior = value_prior(&problem.values, field, groups, candidates);
+    // ожидаемая доля мины по маске допустимых значений; без априорных весов - простое среднее
+    let expected = |domain : u64| -> f64 {
+        let allowed : Vec<usize> = (0..problem.values.len()).filter(|i| domain & (1 << i) != 0).collect();
+        let total : f64 = allowed.iter().map(|i| prior[*i]).sum();
+        if total > 0.0 {
+            return allowed.iter().map(|i| prior[*i] * problem.values[*i] as f64 / full).sum::<f64>() / total;
+        }
+        return allowed.iter().map(|i| problem.values[*i] as f64 / full).sum::<f64>() / allowed.len().max(1) as f64;
+    };
+
+    let all = (1u64 << problem.values.len()) - 1;
+    let mut result : Vec<Option<f64>> = field.tiles.iter()
+        .map(|tile| (tile.status != TileStatus::Opened).then(|| expected(all)))
+        .collect();
+    if !consistent { return result; }
+
+    for vars in problem.components() {
+        let mut total = 0.0;
+        let mut mine = vec![0.0; vars.len()];
+        let complete = problem.enumerate(&vars, SEARCH_BUDGET, |assignment| {
+            let weight : f64 = assignment.iter().map(|value| prior[*value]).product();
+            total += weight;
+            for (i, value) in assignment.iter().enumerate() {
+                mine[i] += weight * problem.values[*value] as f64 / full;
+            }
+        });
+        for (i, &var) in vars.iter().enumerate() {
+            result[problem.tiles[var]] = Some(if complete && total > 0.0 {
+                mine[i] / total
+            } else {
+                expected(problem.domains[var]) // перебор не уложился в бюджет
+            });
+        }
+    }
+    return result;
+}
+
+pub trait Odds {
+    /// Вероятность мины для каждой закрытой клетки с точки зрения игрока (`None` - клетка открыта).
+    /// Игрок видит открытые числа, правила размеров групп и сколько групп и кандидатов на поле;
+    /// все расклады групп, которые с этим сходятся, равновероятны, а какие группы уже измерены, он не знает.
+    /// Расклады считаются точно: решения компонент у открытых чисел складываются вместе и взвешиваются
+    /// числом способов разложить остальное поле на группы так, чтобы сошлись общие числа групп и мин.
+    /// Расклады, которые без учёта чисел в e^50 раз менее вероятны самого вероятного, не учитываются.
+    /// Если сочетаний компонент слишком много, они берутся выборкой. Поля больше `EXACT_TILE_LIMIT` клеток,
+    /// поля после щупа и слишком запутанные компоненты считаются приближённо: клетки независимы,
+    /// с весами ожидаемого числа групп каждого размера.
+    fn mine_odds(&self, groups : usize, candidates : usize) -> Vec<Option<f64>>;
+}
+
+impl Odds for Field {
+    fn mine_odds(&self, groups : usize, candidates : usize) -> Vec<Option<f64>> {
+        let mut problem = Problem::from_field(self);
+        let consistent = problem.propagate();
+        if consistent {
+            if let Some(odds) = Model::new(self, &problem.values, groups, candidates).and_then(|mut model| exact_odds(self, &problem, &mut model)) {
+                return odds;
+            }
+        }
+        return independent_odds(self, &problem, groups, candidates, consistent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // поле 5x3, открыты (0..5, 0), (0, 1) и (1, 1); за ними группы 1/2 {7, 13}, 1/3 {10, 11, 14},
+    // измеренная группа из двух {8 - мина, 12 - пусто} и группа из одной клетки 9
+    fn field_with_groups() -> Field {
+        let mut field = Field::new(5, 3, 0);
+        let groups : [(&[usize], u32); 4] = [(&[7, 13], 2), (&[10, 11, 14], 3), (&[8, 12], 2), (&[9], 1)];
+        for (id, (group, size)) in groups.iter().enumerate() {
+            for &i in group.iter() {
+                field.tiles[i].prob = Prob(Prob::DENOMINATOR / size);
+                field.tiles[i].mine_id = GroupId::new(id);
+            }
+        }
+        field.tiles[8].prob = Prob::MINE;
+        field.tiles[12].prob = Prob::ZERO;
+        for i in [0, 1, 2, 3, 4, 5, 6] {
+            field.tiles[i].status = TileStatus::Opened;
+            field.tiles[i].collapsed = true;
+        }
+        return field;
+    }
+
+    // перебор значений всех закрытых клеток с весом числа способов разбить их на группы
+    fn brute_force(field : &Field, groups : usize, candidates : usize) -> Vec<Option<f64>> {
+        let problem = Problem::from_field(field);
+        let closed : Vec<usize> = (0..field.tiles.len()).filter(|i| field.tiles[*i].status != TileStatus::Opened).collect();
+        let values = &problem.values;
+        let factorial = |n : usize| (1..=n).map(|k| k as f64).product::<f64>();
+        let (min_size, max_size) = (*field.group_sizes.start(), *field.group_sizes.end());
+        let mut total = 0.0;
+        let mut mines = vec![0.0; closed.len()];
+        let mut assignment = vec![0usize; closed.len()];
+        loop {
+            let value_of = |tile : usize| values[assignment[closed.iter().position(|c| *c == tile).unwrap()]];
+            let visible = problem.constraints.iter().all(|c| c.vars.iter().map(|v| value_of(problem.tiles[*v])).sum::<u32>() == c.target);
+            let mut weight = if visible { 1.0 } else { 0.0 };
+            let (mut used, mut tiles, mut mine_tiles) = (0, 0, 0);
+            for (i, &value) in values.iter().enumerate() {
+                let count = assignment.iter().filter(|a| **a == i).count();
+                if value == Prob::MINE.0 { mine_tiles = count; }
+                if i < 2 { continue; }
+                let size = (Prob::DENOMINATOR / value) as usize;
+                if count % size != 0 { weight = 0.0; continue; }
+                let made = count / size;
+                weight *= factorial(count) / (factorial(size).powi(made as i32) * factorial(made));
+                used += made;
+                tiles += count;
+            }
+            let others = groups as isize - used as isize;
+            let rest = candidates as isize - tiles as isize;
+            if others < 0 || mine_tiles != (groups - used.min(groups)) || rest < others * min_size as isize || rest > others * max_size as isize {
+                weight = 0.0;
+            }
+            total += weight;
+            for (i, a) in assignment.iter().enumerate() {
+                mines[i] += weight * values[*a] as f64 / Prob::MINE.0 as f64;
+            }
+            // следующее сочетание значений
+            let Some(i) = assignment.iter().position(|a| *a + 1 < values.len()) else { break; };
+            assignment[i] += 1;
+            assignment[..i].iter_mut().for_each(|a| *a = 0);
+        }
+        let mut result = vec![None; field.tiles.len()];
+        for (i, &tile) in closed.iter().enumerate() {
+            result[tile] = Some(mines[i] / total);
+        }
+        return result;
+    }
+
+    #[test]
+    fn odds_match_brute_force() {
+        let field = field_with_groups();
+        let (groups, candidates) = (4, 8);
+        let expected = brute_force(&field, groups, candidates);
+        let odds = field.mine_odds(groups, candidates);
+        for (index, (a, b)) in odds.iter().zip(&expected).enumerate() {
+            match (a, b) {
+                (Some(a), Some(b)) => assert!((a - b).abs() < 1e-9, "tile {}: {} != {}", index, a, b),
+                _ => assert_eq!(a, b, "tile {}", index)
+            }
+        }
+        // на закрытых клетках все мины поля
+        let sum : f64 = odds.iter().flatten().sum();
+        assert!((sum - groups as f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn group_mates_share_mine() {
+        // одна группа из двух клеток на поле 3x1, открыта середина: число 1/2 видно у обеих соседей
+        let mut field = Field::new(3, 1, 0);
+        field.tiles[1].status = TileStatus::Opened;
+        field.tiles[1].collapsed = true;
+        field.tiles[0].prob = Prob(Prob::DENOMINATOR / 2);
+        field.tiles[2].prob = Prob(Prob::DENOMINATOR / 2);
+        let odds = field.mine_odds(1, 2);
+        assert_eq!(odds[1], None);
+        // обе клетки - одна группа (или измеренная группа с миной в одной из них), мина ровно одна
+        assert!((odds[0].unwrap() + odds[2].unwrap() - 1.0).abs() < 1e-9);
+    }
+}
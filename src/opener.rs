@@ -1,52 +1,151 @@
-use crate::field::{Field, DIRECTIONS};
+use crate::error::EngineError;
+use crate::field::Field;
 use crate::misc::MiscMethods;
 use crate::tile::*;
 use crate::collapser::Collapser;
 
+/// Итог аккорда: изменённые клетки и первая открытая мина, если она была.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Chord {
+    pub changed : Vec<(i32, i32)>,
+    pub exploded : Option<(i32, i32)>
+}
+
 pub trait TileOpener {
-    fn open_tile(&mut self, x : i32, y : i32) -> Result<bool, String>;
-    fn multiopen(&mut self, x: i32, y: i32) -> Result<Vec<(i32, i32)>, String>;
+    fn open_tile(&mut self, x : i32, y : i32) -> Result<bool, EngineError>;
+    /// Каскад от клетки с нулевым числом. Возвращает только клетки, которые он открыл.
+    fn multiopen(&mut self, x: i32, y: i32) -> Result<Vec<(i32, i32)>, EngineError>;
+    fn can_chord(&self, x : i32, y : i32) -> Result<bool, EngineError>;
+    fn chord(&mut self, x : i32, y : i32) -> Result<Chord, EngineError>;
 }
 
 impl TileOpener for Field {
-    fn open_tile(&mut self, x : i32, y : i32) -> Result<bool, String> {
-        let tile = self.get_tile(x, y).ok_or("Invalid coordinates")?;
+    fn open_tile(&mut self, x : i32, y : i32) -> Result<bool, EngineError> {
+        let tile = self.get_tile(x, y).ok_or(EngineError::OutOfBounds { x, y })?;
     
         if tile.status != TileStatus::None {
-            return Err(format!("Tile ({}, {}) already opened or there is a flag on it.", x, y));
+            return Err(EngineError::AlreadyOpened { x, y });
         }
 
-        let _ = self.collapse(x, y);
+        if !tile.collapsed {
+            self.collapse(x, y)?;
+        }
 
-        let tile = self.get_mut_tile(x, y).ok_or("Invalid coordinates")?;
+        let tile = self.get_mut_tile(x, y).ok_or(EngineError::OutOfBounds { x, y })?;
         tile.status = TileStatus::Opened;
-
-        let tile = self.get_tile(x as i32, y as i32).ok_or(format!("Tile ({}, {}) unfound", x, y))?;
-        return Ok(tile.prob >= Prob(12));
+        return Ok(tile.prob >= Prob::MINE);
     }
 
-    fn multiopen(&mut self, x: i32, y: i32) -> Result<Vec<(i32, i32)>, String> {
-        let mut used = Vec::new();
+    fn multiopen(&mut self, x: i32, y: i32) -> Result<Vec<(i32, i32)>, EngineError> {
+        let index = self.coords_to_index(x, y).ok_or(EngineError::OutOfBounds { x, y })?;
+        let mut opened = Vec::new();
+        let mut used = vec![false; self.tiles.len()]; // уже положенные в стек клетки
         let mut stack = Vec::new();
 
         stack.push((x, y));
-        used.push((x, y));
+        used[index] = true;
 
         while let Some((cx, cy)) = stack.pop() {
-            let _ = self.open_tile(cx, cy);
-
-            if self.around_prob_sum(cx, cy)? == Prob(0) {
-                for (dx, dy) in DIRECTIONS {
-                    let nx = cx + dx;
-                    let ny = cy + dy;
-                    if self.is_inside_bounds(nx, ny) && !used.contains(&(nx, ny)) {
-                        used.push((nx, ny));
+            // открытые клетки и клетки с флажками каскад не трогает, но проходит через них
+            if self.get_tile(cx, cy).map(|t| &t.status) == Some(&TileStatus::None) {
+                self.open_tile(cx, cy)?;
+                opened.push((cx, cy));
+            }
+
+            if self.around_prob_sum(cx, cy)? == Prob::ZERO {
+                for (nx, ny) in self.neighbours(cx, cy) {
+                    let Some(n) = self.coords_to_index(nx, ny) else { continue; };
+                    if !used[n] {
+                        used[n] = true;
                         stack.push((nx, ny));
                     }
                 }
             }
         }
 
-        return Ok(used);
-    }   
+        return Ok(opened);
+    }
+
+    /// Можно ли аккордить открытую клетку: флажки вокруг полностью объясняют её число.
+    /// Обычный флажок считается целой миной (`Prob::MINE`), квантовый - ровно вероятностью своей клетки,
+    /// и после них от числа ничего не должно остаться. Тогда при верных флажках все остальные
+    /// закрытые соседи безопасны.
+    fn can_chord(&self, x : i32, y : i32) -> Result<bool, EngineError> {
+        let tile = self.get_tile(x, y).ok_or(EngineError::OutOfBounds { x, y })?;
+        if tile.status != TileStatus::Opened { return Ok(false); }
+
+        let mut covered = 0u32;
+        for (nx, ny) in self.neighbours(x, y) {
+            match self.get_tile(nx, ny) {
+                Some(Tile { status: TileStatus::Flag, .. }) => covered += Prob::MINE.0,
+                Some(Tile { status: TileStatus::QuantFlag, prob, .. }) => covered += prob.0,
+                _ => {}
+            }
+        }
+        return Ok(self.around_prob_sum(x, y)?.0 == covered);
+    }
+
+    /// Аккорд: открывает все закрытые клетки без флажков вокруг открытой клетки (с каскадом `multiopen`).
+    /// Если аккорд невозможен, ничего не делает.
+    fn chord(&mut self, x : i32, y : i32) -> Result<Chord, EngineError> {
+        let mut result = Chord::default();
+        if !self.can_chord(x, y)? { return Ok(result); }
+
+        for (nx, ny) in self.neighbours(x, y) {
+            if self.get_tile(nx, ny).map(|t| &t.status) != Some(&TileStatus::None) { continue; }
+            let exploded = self.open_tile(nx, ny)?;
+            result.changed.push((nx, ny));
+            if exploded {
+                result.exploded = result.exploded.or(Some((nx, ny)));
+            } else {
+                result.changed.extend(self.multiopen(nx, ny)?);
+            }
+        }
+        return Ok(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // поле 5x5: открытая (0, 0), рядом с ней две клетки группы из 4 с одной миной, сумма 1/2
+    fn field_with_group() -> Field {
+        let mut field = Field::new(5, 5, 0);
+        let group = vec![5, 6, 23, 24]; // (0, 1), (1, 1), (3, 4), (4, 4)
+        for &i in &group {
+            field.tiles[i].prob = Prob(Prob::DENOMINATOR / 4);
+            field.tiles[i].mine_id = Some(GroupId(0));
+        }
+        field.group_tiles = vec![group];
+        field.tiles[0].status = TileStatus::Opened;
+        field.tiles[0].collapsed = true;
+        return field;
+    }
+
+    #[test]
+    fn chord_refused_while_sum_unexplained() {
+        let mut field = field_with_group();
+        field.set_tile_status(0, 1, TileStatus::QuantFlag);
+        // квантовый флажок покрывает только 1/4 из 1/2: (1, 1) ещё может быть миной
+        assert!(!field.can_chord(0, 0).unwrap());
+        assert_eq!(field.chord(0, 0).unwrap(), Chord::default());
+        assert_eq!(field.tiles[1].status, TileStatus::None);
+
+        field.set_tile_status(1, 1, TileStatus::Flag);
+        // обычный флажок считается целой миной, и флажков становится больше числа
+        assert!(!field.can_chord(0, 0).unwrap());
+    }
+
+    #[test]
+    fn chord_allowed_when_flags_explain_sum() {
+        let mut field = field_with_group();
+        field.set_tile_status(0, 1, TileStatus::QuantFlag);
+        field.set_tile_status(1, 1, TileStatus::QuantFlag);
+        assert!(field.can_chord(0, 0).unwrap());
+        let chord = field.chord(0, 0).unwrap();
+        assert_eq!(chord.exploded, None);
+        assert!(chord.changed.contains(&(1, 0)));
+        assert_eq!(field.tiles[1].status, TileStatus::Opened);
+    }
 }
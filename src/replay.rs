@@ -0,0 +1,355 @@
+use crate::bytes::{ByteReader, Byter};
+use crate::engine::{GameConfig, GameEngine, ToolType};
+use crate::error::EngineError;
+use crate::field::{Field, Tiling, Topology};
+
+const TEXT_HEADER : &str = "quantsweeper-replay";
+const PROBE_TAG : u8 = 7;
+
+/// Один ход игрока в том виде, в котором он пришёл в движок.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Move {
+    Interact { x : i32, y : i32, tool : ToolType },
+    Collapse,
+    Undo,
+    Redo,
+    Hint
+}
+
+impl Move {
+    // инструменты 0-2 пишутся своим номером; номера 3-6 заняты, поэтому у щупа тег `PROBE_TAG`
+    fn tag(&self) -> u8 {
+        match self {
+            Move::Interact { tool: ToolType::Probe, .. } => return PROBE_TAG,
+            Move::Interact { tool, .. } => return tool.to_u8(),
+            Move::Collapse => return 3,
+            Move::Undo => return 4,
+            Move::Redo => return 5,
+            Move::Hint => return 6
+        }
+    }
+
+    // o/f/q/p x y - лопата, флажок, квантовый флажок, щуп; c - коллапс; u/r - отмена/повтор; h - подсказка
+    fn to_text(self) -> String {
+        match self {
+            Move::Interact { x, y, tool } => {
+                let letter = match tool {
+                    ToolType::Shovel => 'o',
+                    ToolType::SimpleFlag => 'f',
+                    ToolType::QuantFlag => 'q',
+                    ToolType::Probe => 'p'
+                };
+                return format!("{} {} {}", letter, x, y);
+            },
+            Move::Collapse => return String::from("c"),
+            Move::Undo => return String::from("u"),
+            Move::Redo => return String::from("r"),
+            Move::Hint => return String::from("h")
+        }
+    }
+
+    fn from_text(line : &str) -> Result<Move, EngineError> {
+        let parts : Vec<&str> = line.split_whitespace().collect();
+        let tool = match parts.as_slice() {
+            ["c"] => return Ok(Move::Collapse),
+            ["u"] => return Ok(Move::Undo),
+            ["r"] => return Ok(Move::Redo),
+            ["h"] => return Ok(Move::Hint),
+            ["o", _, _] => ToolType::Shovel,
+            ["f", _, _] => ToolType::SimpleFlag,
+            ["q", _, _] => ToolType::QuantFlag,
+            ["p", _, _] => ToolType::Probe,
+            _ => return Err(EngineError::InvalidReplay(format!("invalid move '{}'", line)))
+        };
+        let x = parts[1].parse().map_err(|_| EngineError::InvalidReplay(format!("invalid x in move '{}'", line)))?;
+        let y = parts[2].parse().map_err(|_| EngineError::InvalidReplay(format!("invalid y in move '{}'", line)))?;
+        return Ok(Move::Interact { x, y, tool });
+    }
+}
+
+/// Запись партии: конфиг с сидом и все ходы по порядку. По ней игра восстанавливается точно.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub config : GameConfig,
+    pub moves : Vec<Move>
+}
+
+impl Replay {
+    /// `config.seed` должен быть задан, иначе повтор сгенерирует другое поле.
+    pub fn new(config : GameConfig) -> Replay {
+        return Replay { config, moves: Vec::new() };
+    }
+
+    pub fn push(&mut self, game_move : Move) {
+        self.moves.push(game_move);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.moves.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.moves.is_empty();
+    }
+
+    pub fn to_text(&self) -> String {
+        let config = &self.config;
+        let mut lines = vec![
+            format!("{} 1", TEXT_HEADER),
+            format!("config width={} height={} groups={} candidates={} seed={} undo={} no_guess={} attempts={} hint_cost={} topology={} tiling={} min_group={} max_group={} group_mines={} entangled={} probes={} huge={}",
+                config.width, config.height, config.groups, config.candidates, config.seed.unwrap_or(0),
+                config.undo as u8, config.no_guess as u8, config.generation_attempts, config.hint_cost, config.topology.name(), config.tiling.name(),
+                config.min_group_size, config.max_group_size, config.group_mines, config.entangled, config.probes, config.huge as u8)
+        ];
+        lines.extend(self.moves.iter().map(|m| m.to_text()));
+        return lines.join("\n");
+    }
+
+    pub fn from_text(text : &str) -> Result<Replay, EngineError> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next() != Some(&format!("{} 1", TEXT_HEADER)) {
+            return Err(EngineError::InvalidReplay(String::from("it isn't a quantsweeper replay (version 1)")));
+        }
+        let config_line = lines.next().ok_or(EngineError::InvalidReplay(String::from("config is missing")))?;
+        let config = config_from_text(config_line)?;
+        let moves = lines.map(Move::from_text).collect::<Result<Vec<Move>, EngineError>>()?;
+        return Ok(Replay { config, moves });
+    }
+}
+
+// config ключ=значение ...; отсутствующие необязательные ключи берутся по умолчанию
+fn config_from_text(line : &str) -> Result<GameConfig, EngineError> {
+    let mut parts = line.split_whitespace();
+    if parts.next() != Some("config") {
+        return Err(EngineError::InvalidReplay(format!("invalid config '{}'", line)));
+    }
+    let mut config = GameConfig::new(0, 0, 0.0, 0.0);
+    let mut required = 0;
+    for part in parts {
+        let (key, value) = part.split_once('=').ok_or(EngineError::InvalidReplay(format!("invalid config entry '{}'", part)))?;
+        let invalid = || EngineError::InvalidReplay(format!("invalid value of '{}' in config", key));
+        match key {
+            "width" => { config.width = value.parse().map_err(|_| invalid())?; required += 1; },
+            "height" => { config.height = value.parse().map_err(|_| invalid())?; required += 1; },
+            "groups" => { config.groups = value.parse().map_err(|_| invalid())?; required += 1; },
+            "candidates" => { config.candidates = value.parse().map_err(|_| invalid())?; required += 1; },
+            "seed" => { config.seed = Some(value.parse().map_err(|_| invalid())?); required += 1; },
+            "undo" => config.undo = value == "1",
+            "no_guess" => config.no_guess = value == "1",
+            "attempts" => config.generation_attempts = value.parse().map_err(|_| invalid())?,
+            "hint_cost" => config.hint_cost = value.parse().map_err(|_| invalid())?,
+            "topology" => config.topology = Topology::from_name(value).ok_or_else(invalid)?,
+            "tiling" => config.tiling = Tiling::from_name(value).ok_or_else(invalid)?,
+            "min_group" => config.min_group_size = value.parse().map_err(|_| invalid())?,
+            "max_group" => config.max_group_size = value.parse().map_err(|_| invalid())?,
+            "group_mines" => config.group_mines = value.parse().map_err(|_| invalid())?,
+            "entangled" => config.entangled = value.parse().map_err(|_| invalid())?,
+            "probes" => config.probes = value.parse().map_err(|_| invalid())?,
+            "huge" => config.huge = value == "1",
+            _ => return Err(EngineError::InvalidReplay(format!("unknown config key '{}'", key)))
+        }
+    }
+    if required != 5 {
+        return Err(EngineError::InvalidReplay(String::from("config needs width, height, groups, candidates and seed")));
+    }
+    return Ok(config);
+}
+
+impl Byter for Replay {
+    const MAGIC : &'static [u8; 4] = b"QSRP";
+
+    // конфиг | число ходов (u32) | ходы: тег (u8) [+ x (i32) + y (i32) для клика]
+    fn write_bytes(&self, out : &mut Vec<u8>) {
+        self.config.write_bytes(out);
+        out.extend((self.moves.len() as u32).to_le_bytes());
+        for game_move in &self.moves {
+            out.push(game_move.tag());
+            if let Move::Interact { x, y, .. } = game_move {
+                out.extend(x.to_le_bytes());
+                out.extend(y.to_le_bytes());
+            }
+        }
+    }
+
+    fn read_bytes(reader : &mut ByteReader) -> Result<Replay, EngineError> {
+        let config = GameConfig::read_bytes(reader)?;
+        let count = reader.u32()? as usize;
+        // каждый ход занимает хотя бы байт - не даём испорченному счётчику заказать лишнюю память
+        let mut moves = Vec::with_capacity(count.min(reader.remaining()));
+        for _ in 0..count {
+            let game_move = match reader.u8()? {
+                3 => Move::Collapse,
+                4 => Move::Undo,
+                5 => Move::Redo,
+                6 => Move::Hint,
+                tag => {
+                    let tool = if tag == PROBE_TAG { ToolType::Probe } else { ToolType::from_u8(tag)? };
+                    let x = reader.u32()? as i32;
+                    let y = reader.u32()? as i32;
+                    Move::Interact { x, y, tool }
+                }
+            };
+            moves.push(game_move);
+        }
+        return Ok(Replay { config, moves });
+    }
+}
+
+/// Проигрывает запись и отдаёт состояние игры после любого числа ходов.
+pub struct ReplayPlayer {
+    replay : Replay,
+    engine : GameEngine,
+    position : usize
+}
+
+impl ReplayPlayer {
+    pub fn new(replay : Replay) -> Result<ReplayPlayer, EngineError> {
+        let mut engine = GameEngine::new();
+        engine.start_new_game(replay.config.clone())?;
+        return Ok(ReplayPlayer { replay, engine, position: 0 });
+    }
+
+    pub fn replay(&self) -> &Replay {
+        return &self.replay;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.replay.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.replay.is_empty();
+    }
+
+    /// Сколько ходов уже применено.
+    pub fn position(&self) -> usize {
+        return self.position;
+    }
+
+    pub fn engine(&self) -> &GameEngine {
+        return &self.engine;
+    }
+
+    /// Переходит к состоянию после `index` ходов. Назад - переигрыванием с начала.
+    /// Ошибки отдельных ходов игнорируются: движок детерминирован, и в исходной партии они были теми же.
+    pub fn seek(&mut self, index : usize) -> Result<&GameEngine, EngineError> {
+        if index > self.replay.len() {
+            return Err(EngineError::InvalidReplay(format!("replay has only {} moves, requested {}", self.replay.len(), index)));
+        }
+        if index < self.position {
+            self.engine.start_new_game(self.replay.config.clone())?;
+            self.position = 0;
+        }
+        while self.position < index {
+            let game_move = self.replay.moves[self.position];
+            let _ = match game_move {
+                Move::Interact { x, y, tool } => {
+                    self.engine.change_tool(tool);
+                    self.engine.tile_interact(x, y)
+                },
+                Move::Collapse => self.engine.collapse_quant_flags(),
+                Move::Undo => self.engine.undo().map(|_| ()),
+                Move::Redo => self.engine.redo().map(|_| ()),
+                Move::Hint => self.engine.hint().map(|_| ())
+            };
+            self.position += 1;
+        }
+        return Ok(&self.engine);
+    }
+
+    /// Поле после `index` ходов.
+    pub fn field_at(&mut self, index : usize) -> Result<Field, EngineError> {
+        return self.seek(index)?.field().cloned().ok_or(EngineError::NoField);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::TileStatus;
+
+    // партия со всеми видами ходов; запись берётся из самого движка
+    fn played_engine() -> GameEngine {
+        let config = GameConfig::builder(10, 10).groups(0.12).candidates(0.3).seed(23).probes(1).entangled(0.5).hint_cost(1).build().unwrap();
+        let mut engine = GameEngine::new();
+        engine.start_new_game(config).unwrap();
+        engine.tile_interact(5, 5).unwrap();
+        let closed : Vec<(i32, i32)> = (0..100).map(|i| (i % 10, i / 10))
+            .filter(|&(x, y)| engine.get_tile_status(x, y) == Ok(TileStatus::None)).collect();
+        let tools = [ToolType::QuantFlag, ToolType::Probe, ToolType::QuantFlag, ToolType::SimpleFlag];
+        for (&(x, y), tool) in closed.iter().zip(tools) {
+            engine.change_tool(tool);
+            let _ = engine.tile_interact(x, y);
+        }
+        let _ = engine.hint();
+        let _ = engine.collapse_quant_flags();
+        let _ = engine.undo();
+        let _ = engine.redo();
+        return engine;
+    }
+
+    fn assert_same_game(replayed : &GameEngine, original : &GameEngine) {
+        assert_eq!(replayed.field().unwrap().tiles, original.field().unwrap().tiles);
+        assert_eq!(replayed.state(), original.state());
+        assert_eq!(replayed.quant_flags(), original.quant_flags());
+        assert_eq!(replayed.probes(), original.probes());
+    }
+
+    #[test]
+    fn replay_reproduces_game() {
+        let original = played_engine();
+        let replay = original.replay().unwrap().clone();
+        assert!(replay.len() > 5);
+        let mut player = ReplayPlayer::new(replay.clone()).unwrap();
+        assert_same_game(player.seek(replay.len()).unwrap(), &original);
+
+        // перемотка назад переигрывает с начала и приходит к тому же полю
+        let middle = player.field_at(3).unwrap();
+        player.seek(replay.len()).unwrap();
+        assert_eq!(player.field_at(3).unwrap().tiles, middle.tiles);
+        assert_same_game(player.seek(replay.len()).unwrap(), &original);
+        assert!(player.seek(replay.len() + 1).is_err());
+    }
+
+    #[test]
+    fn rejected_generation_leaves_no_trace() {
+        let config = GameConfig::builder(12, 12).seed(0).groups(0.15).candidates(0.45).no_guess(true).generation_attempts(1).build().unwrap();
+        let mut engine = GameEngine::new();
+        engine.start_new_game(config).unwrap();
+        // неудачные попытки генерации не попадают ни в запись, ни в историю и не сдвигают генератор поля
+        let mut failed = 0;
+        for x in (0..12).rev() {
+            match engine.tile_interact(x, 6) {
+                Ok(()) => break,
+                Err(error) => assert_eq!(error, EngineError::GenerationFailed { attempts: 1 })
+            }
+            failed += 1;
+        }
+        assert!(failed > 0 && failed < 12);
+        let generated = engine.field().unwrap().tiles.clone();
+        assert!(engine.undo().unwrap());
+        assert!(!engine.can_undo());
+        assert!(engine.redo().unwrap());
+
+        let replay = engine.replay().unwrap().clone();
+        assert_eq!(replay.len(), 3);
+        let mut player = ReplayPlayer::new(replay).unwrap();
+        assert_eq!(player.field_at(1).unwrap().tiles, generated);
+        assert_same_game(player.seek(3).unwrap(), &engine);
+    }
+
+    #[test]
+    fn text_and_bytes_round_trip() {
+        let original = played_engine();
+        let replay = original.replay().unwrap();
+        let from_text = Replay::from_text(&replay.to_text()).unwrap();
+        let from_bytes = Replay::from_bytes(&replay.to_bytes()).unwrap();
+        assert_eq!(&from_text, replay);
+        assert_eq!(&from_bytes, replay);
+        for decoded in [from_text, from_bytes] {
+            let len = decoded.len();
+            let mut player = ReplayPlayer::new(decoded).unwrap();
+            assert_same_game(player.seek(len).unwrap(), &original);
+        }
+    }
+}
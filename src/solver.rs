@@ -0,0 +1,451 @@
+use crate::collapser::Collapser;
+use crate::field::Field;
+use crate::generator::{group_probs, probed_group_probs};
+use crate::misc::MiscMethods;
+use crate::opener::TileOpener;
+use crate::tile::*;
+
+/// Сколько узлов перебора допускается на одну компоненту. Если не хватило,
+/// для компоненты остаются выводы одного распространения ограничений (они тоже верны).
+pub const SEARCH_BUDGET : usize = 20_000;
+
+/// Что игрок может наверняка сказать о закрытой клетке.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeductionKind {
+    Safe,                   // мины здесь нет ни в одном варианте
+    Mine,                   // мина здесь при любом коллапсе
+    Candidate(Option<Prob>) // клетка точно в группе; вероятность, если она однозначна
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deduction {
+    pub x : i32,
+    pub y : i32,
+    pub kind : DeductionKind,
+    pub reasons : Vec<(i32, i32)> // открытые соседи, из чисел которых это следует
+}
+
+/// Ограничение от открытой клетки: сумма вероятностей закрытых соседей равна `target`.
+#[derive(Debug, Clone)]
+pub struct Constraint {
+    pub tile : usize,
+    pub vars : Vec<usize>,
+    pub target : u32
+}
+
+/// Задача в терминах того, что видит игрок: закрытые клетки у открытых чисел и их возможные значения.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub values : Vec<u32>,       // все возможные вероятности клетки (в долях `Prob::DENOMINATOR`), values[0] == 0
+    pub tiles : Vec<usize>,      // индекс клетки поля для каждой переменной
+    pub domains : Vec<u64>,      // маска допустимых индексов `values` для каждой переменной
+    pub constraints : Vec<Constraint>,
+    pub var_constraints : Vec<Vec<usize>>
+}
+
+impl Problem {
+    /// Собирает задачу только из видимого игроку: статусов и чисел на открытых клетках.
+    /// Коллапс закрытой клетки игроку не виден, поэтому любая закрытая клетка может иметь любое значение.
+    pub fn from_field(field : &Field) -> Problem {
+        // 0, целая мина (после коллапса) и вероятности групп допустимых размеров (после щупа - любые доли)
+        let mut values = vec![0, Prob::MINE.0];
+        let probs = if field.probed {
+            probed_group_probs(&field.group_sizes, field.group_mines)
+        } else {
+            group_probs(&field.group_sizes, field.group_mines)
+        };
+        for prob in probs {
+            if !values.contains(&prob.0) { values.push(prob.0); }
+        }
+        let any = (1u64 << values.len()) - 1;
+
+        let mut var_of = vec![usize::MAX; field.tiles.len()];
+        let mut problem = Problem { values, tiles: Vec::new(), domains: Vec::new(), constraints: Vec::new(), var_constraints: Vec::new() };
+
+        for (index, tile) in field.tiles.iter().enumerate() {
+            if tile.status != TileStatus::Opened { continue; }
+            let (x, y) = index_to_coords(field, index);
+            let mut target = match field.around_prob_sum(x, y) { Ok(sum) => sum.0, Err(_) => continue };
+            let mut vars = Vec::new();
+            for (nx, ny) in field.neighbours(x, y) {
+                let Some(neighbour_index) = field.coords_to_index(nx, ny) else { continue; };
+                let neighbour = &field.tiles[neighbour_index];
+                if neighbour.status == TileStatus::Opened {
+                    target = target.saturating_sub(neighbour.prob.0); // открытая клетка видна целиком
+                    continue;
+                }
+                if var_of[neighbour_index] == usize::MAX {
+                    var_of[neighbour_index] = problem.tiles.len();
+                    problem.tiles.push(neighbour_index);
+                    problem.domains.push(any);
+                    problem.var_constraints.push(Vec::new());
+                }
+                vars.push(var_of[neighbour_index]);
+            }
+            if vars.is_empty() { continue; }
+            for &var in &vars {
+                problem.var_constraints[var].push(problem.constraints.len());
+            }
+            problem.constraints.push(Constraint { tile: index, vars, target });
+        }
+        return problem;
+    }
+
+    /// Отсекает значения, которые не дают ни одного решения какого-то ограничения. `false` - противоречие.
+    pub fn propagate(&mut self) -> bool {
+        let mut queue : Vec<usize> = (0..self.constraints.len()).collect();
+        let mut queued = vec![true; self.constraints.len()];
+        while let Some(c) = queue.pop() {
+            queued[c] = false;
+            let constraint = &self.constraints[c];
+            let vars = constraint.vars.clone();
+            let target = constraint.target;
+            // суммы префиксов и суффиксов, чтобы для каждой переменной знать суммы остальных
+            let mut prefix = vec![vec![0u32]];
+            for &var in &vars {
+                let next = self.extend_sums(prefix.last().unwrap(), self.domains[var], target);
+                prefix.push(next);
+            }
+            let mut suffix = vec![vec![0u32]; vars.len() + 1];
+            for i in (0..vars.len()).rev() {
+                suffix[i] = self.extend_sums(&suffix[i + 1], self.domains[vars[i]], target);
+            }
+            for (i, &var) in vars.iter().enumerate() {
+                let mut allowed = 0u64;
+                for (value_index, &value) in self.values.iter().enumerate() {
+                    if self.domains[var] & (1 << value_index) == 0 || value > target { continue; }
+                    let rest = target - value;
+                    if prefix[i].iter().any(|p| *p <= rest && suffix[i + 1].binary_search(&(rest - p)).is_ok()) {
+                        allowed |= 1 << value_index;
+                    }
+                }
+                if allowed == 0 { return false; }
+                if allowed != self.domains[var] {
+                    self.domains[var] = allowed;
+                    for &other in &self.var_constraints[var] {
+                        if !queued[other] {
+                            queued[other] = true;
+                            queue.push(other);
+                        }
+                    }
+                }
+            }
+        }
+        return true;
+    }
+
+    fn extend_sums(&self, sums : &[u32], domain : u64, target : u32) -> Vec<u32> {
+        let mut result : Vec<u32> = sums.iter()
+            .flat_map(|s| self.values.iter().enumerate()
+                .filter(move |(i, _)| domain & (1 << i) != 0)
+                .map(move |(_, v)| s + v))
+            .filter(|s| *s <= target)
+            .collect();
+        result.sort_unstable();
+        result.dedup();
+        return result;
+    }
+
+    /// Разбивает переменные на независимые группы (связанные общими ограничениями).
+    pub fn components(&self) -> Vec<Vec<usize>> {
+        let mut component = vec![usize::MAX; self.tiles.len()];
+        let mut result = Vec::new();
+        for start in 0..self.tiles.len() {
+            if component[start] != usize::MAX { continue; }
+            let mut members = vec![start];
+            component[start] = result.len();
+            let mut i = 0;
+            while i < members.len() {
+                let var = members[i];
+                for &c in &self.var_constraints[var] {
+                    for &other in &self.constraints[c].vars {
+                        if component[other] == usize::MAX {
+                            component[other] = result.len();
+                            members.push(other);
+                        }
+                    }
+                }
+                i += 1;
+            }
+            result.push(members);
+        }
+        return result;
+    }
+
+    /// Перебирает все решения компоненты, вызывая `visit` с индексами значений переменных `vars`.
+    /// Возвращает `false`, если бюджет узлов кончился раньше.
+    pub fn enumerate(&self, vars : &[usize], budget : usize, mut visit : impl FnMut(&[usize])) -> bool {
+        let mut search = Search {
+            problem: self,
+            vars,
+            position: vars.iter().enumerate().fold(vec![usize::MAX; self.tiles.len()], |mut acc, (i, v)| { acc[*v] = i; acc }),
+            assignment: vec![usize::MAX; vars.len()],
+            nodes: 0,
+            budget
+        };
+        return search.run(0, &mut visit);
+    }
+
+    fn min_max(&self, var : usize) -> (u32, u32) {
+        let mut values = self.values.iter().enumerate().filter(|(i, _)| self.domains[var] & (1 << i) != 0).map(|(_, v)| *v);
+        let first = values.next().unwrap_or(0);
+        return values.fold((first, first), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    }
+}
+
+struct Search<'a> {
+    problem : &'a Problem,
+    vars : &'a [usize],
+    position : Vec<usize>,
+    assignment : Vec<usize>,
+    nodes : usize,
+    budget : usize
+}
+
+impl Search<'_> {
+    fn run(&mut self, depth : usize, visit : &mut impl FnMut(&[usize])) -> bool {
+        if depth == self.vars.len() {
+            visit(&self.assignment);
+            return true;
+        }
+        let var = self.vars[depth];
+        for value_index in 0..self.problem.values.len() {
+            if self.problem.domains[var] & (1 << value_index) == 0 { continue; }
+            self.nodes += 1;
+            if self.nodes > self.budget { return false; }
+            self.assignment[depth] = value_index;
+            if self.feasible(var) && !self.run(depth + 1, visit) {
+                return false;
+            }
+        }
+        self.assignment[depth] = usize::MAX;
+        return true;
+    }
+
+    // проверяет ограничения переменной по границам ещё не назначенных соседей
+    fn feasible(&self, var : usize) -> bool {
+        for &c in &self.problem.var_constraints[var] {
+            let constraint = &self.problem.constraints[c];
+            let (mut lo, mut hi) = (0, 0);
+            for &other in &constraint.vars {
+                let assigned = self.assignment[self.position[other]];
+                if assigned != usize::MAX {
+                    lo += self.problem.values[assigned];
+                    hi += self.problem.values[assigned];
+                } else {
+                    let (min, max) = self.problem.min_max(other);
+                    lo += min;
+                    hi += max;
+                }
+            }
+            if constraint.target < lo || constraint.target > hi { return false; }
+        }
+        return true;
+    }
+}
+
+pub fn index_to_coords(field : &Field, index : usize) -> (i32, i32) {
+    return ((index as u32 % field.width) as i32, (index as u32 / field.width) as i32);
+}
+
+pub trait Solver {
+    /// Все выводы, доступные игроку по открытым числам: безопасные клетки, мины и точные кандидаты.
+    fn deductions(&self) -> Vec<Deduction>;
+    /// Задача с доменами, суженными до значений из реальных решений (где хватило бюджета).
+    /// `None`, если открытые числа противоречат друг другу.
+    fn solve_problem(&self) -> Option<Problem>;
+}
+
+impl Solver for Field {
+    fn solve_problem(&self) -> Option<Problem> {
+        let mut problem = Problem::from_field(self);
+        if !problem.propagate() { return None; }
+        for vars in problem.components() {
+            let mut seen = vec![0u64; vars.len()];
+            let complete = problem.enumerate(&vars, SEARCH_BUDGET, |assignment| {
+                for (i, value_index) in assignment.iter().enumerate() {
+                    seen[i] |= 1 << value_index;
+                }
+            });
+            if complete {
+                if seen.contains(&0) { return None; }
+                for (i, &var) in vars.iter().enumerate() {
+                    problem.domains[var] = seen[i];
+                }
+            }
+        }
+        return Some(problem);
+    }
+
+    fn deductions(&self) -> Vec<Deduction> {
+        let Some(problem) = self.solve_problem() else { return Vec::new(); };
+        let full = problem.values.iter().position(|v| *v == Prob::MINE.0).unwrap();
+        let mut result = Vec::new();
+        for (var, &index) in problem.tiles.iter().enumerate() {
+            let domain = problem.domains[var];
+            let kind = if domain == 1 {
+                DeductionKind::Safe
+            } else if domain == 1 << full {
+                DeductionKind::Mine
+            } else if domain & 1 == 0 {
+                let exact = (domain.count_ones() == 1).then(|| Prob(problem.values[domain.trailing_zeros() as usize]));
+                DeductionKind::Candidate(exact)
+            } else {
+                continue;
+            };
+            let (x, y) = index_to_coords(self, index);
+            let reasons = problem.var_constraints[var].iter()
+                .map(|c| index_to_coords(self, problem.constraints[*c].tile))
+                .collect();
+            result.push(Deduction { x, y, kind, reasons });
+        }
+        return result;
+    }
+}
+
+/// Итог прохождения поля игроком, который знает только то, что видно на экране.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Simulation {
+    pub solved : bool,
+    pub measurements : usize, // потрачено квантовых флажков
+    pub guesses : usize,      // ходов наугад (только при `allow_guesses`)
+    pub steps : usize         // сколько раз перерешивалось поле
+}
+
+/// Проходит поле с первого клика: открывает всё, что точно безопасно; когда выводов нет,
+/// тратит квантовый флажок на измерение группы точного кандидата; если измерять нечего
+/// и `allow_guesses`, открывает закрытую клетку наугад. Исходы измерений берутся из `seed`.
+/// Коллапс клеток, которые игрок не измерял сам, ему не виден: измерение уже схлопнутой клетки
+/// тратит флажок впустую, как и в игре.
+/// Каждый шаг перерешивает всё поле, поэтому шагов не больше `max_steps`; `None`, если их не хватило.
+pub fn simulate(field : &Field, first_x : i32, first_y : i32, quant_flags : usize, seed : u64, allow_guesses : bool,
+    max_steps : usize) -> Option<Simulation> {
+    let mut field = field.clone();
+    field.rng.seed(seed);
+    let mut result = Simulation { solved: false, measurements: 0, guesses: 0, steps: 0 };
+    let mut flags = quant_flags;
+    let mut measured = vec![false; field.tiles.len()]; // клетки, которые игрок уже измерял
+    if open_with_cascade(&mut field, first_x, first_y) { return Some(result); }
+
+    while result.steps < max_steps {
+        result.steps += 1;
+        if field.tiles.iter().all(|tile| tile.status == TileStatus::Opened || tile.prob == Prob::MINE) {
+            result.solved = true;
+            return Some(result);
+        }
+        let deductions = field.deductions();
+        let closed = |field : &Field, d : &&Deduction| field.get_tile(d.x, d.y).is_some_and(|t| t.status != TileStatus::Opened);
+
+        let safe : Vec<(i32, i32)> = deductions.iter()
+            .filter(|d| d.kind == DeductionKind::Safe && closed(&field, d))
+            .map(|d| (d.x, d.y)).collect();
+        if !safe.is_empty() {
+            for (x, y) in safe {
+                if open_with_cascade(&mut field, x, y) { return Some(result); }
+            }
+            continue;
+        }
+
+        if flags > 0 {
+            // сначала клетки, которые точно в группе, потом любые неизмеренные у границы
+            let unmeasured = |x : i32, y : i32| field.coords_to_index(x, y).is_some_and(|i| !measured[i]);
+            let candidate = deductions.iter()
+                .filter(|d| matches!(d.kind, DeductionKind::Candidate(_)) && closed(&field, d))
+                .find(|d| unmeasured(d.x, d.y))
+                .map(|d| (d.x, d.y))
+                .or_else(|| frontier(&field).into_iter().find(|(x, y)| unmeasured(*x, *y)));
+            if let Some((x, y)) = candidate {
+                if let Some(index) = field.coords_to_index(x, y) {
+                    measured[index] = true;
+                }
+                let _ = field.collapse(x, y);
+                flags -= 1;
+                result.measurements += 1;
+                continue;
+            }
+        }
+
+        if !allow_guesses { return Some(result); }
+        let known_mines : Vec<(i32, i32)> = deductions.iter().filter(|d| d.kind == DeductionKind::Mine).map(|d| (d.x, d.y)).collect();
+        let guess = frontier(&field).into_iter().chain(
+                (0..field.tiles.len()).map(|i| index_to_coords(&field, i)))
+            .find(|(x, y)| !known_mines.contains(&(*x, *y)) && field.get_tile(*x, *y).is_some_and(|t| t.status != TileStatus::Opened));
+        let Some((x, y)) = guess else { return Some(result); };
+        result.guesses += 1;
+        // если там мина, считаем её найденной ценой угадывания: она открыта и видна дальше
+        open_with_cascade(&mut field, x, y);
+    }
+    return None;
+}
+
+// открывает клетку и, если вокруг ноль, всю область; `true`, если под клеткой мина
+fn open_with_cascade(field : &mut Field, x : i32, y : i32) -> bool {
+    match field.open_tile(x, y) {
+        Ok(true) => return true,
+        Ok(false) => { let _ = field.multiopen(x, y); },
+        Err(_) => {}
+    }
+    return false;
+}
+
+// закрытые клетки рядом с открытыми
+fn frontier(field : &Field) -> Vec<(i32, i32)> {
+    let problem = Problem::from_field(field);
+    return problem.tiles.iter().map(|i| index_to_coords(field, *i)).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::Generator;
+
+    // каждый вывод должен совпадать с настоящим полем, которое игроку не видно
+    fn check_deductions(field : &Field, deductions : &[Deduction]) {
+        for d in deductions {
+            let tile = field.get_tile(d.x, d.y).unwrap();
+            match &d.kind {
+                DeductionKind::Safe => assert_eq!(tile.prob, Prob::ZERO, "safe ({}, {}) is not safe", d.x, d.y),
+                DeductionKind::Mine => assert_eq!(tile.prob, Prob::MINE, "mine ({}, {}) is not a mine", d.x, d.y),
+                DeductionKind::Candidate(exact) => {
+                    assert!(tile.mine_id.is_some() && tile.prob != Prob::ZERO, "candidate ({}, {}) is not in a group", d.x, d.y);
+                    if let Some(prob) = exact {
+                        assert_eq!(&tile.prob, prob, "candidate ({}, {}) has another probability", d.x, d.y);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deductions_are_sound() {
+        let mut checked = 0;
+        for seed in 0..40 {
+            let mut field = Field::new(12, 12, seed);
+            field.generate(6, 6, 0.1, 0.25).unwrap();
+            if open_with_cascade(&mut field, 6, 6) { continue; }
+            // ходим как `simulate`: открываем безопасные, иначе измеряем кандидата
+            for _ in 0..60 {
+                let deductions = field.deductions();
+                check_deductions(&field, &deductions);
+                checked += deductions.len();
+                let closed = |d : &&Deduction| field.get_tile(d.x, d.y).is_some_and(|t| t.status != TileStatus::Opened);
+                let safe : Vec<(i32, i32)> = deductions.iter()
+                    .filter(|d| d.kind == DeductionKind::Safe).filter(closed)
+                    .map(|d| (d.x, d.y)).collect();
+                if !safe.is_empty() {
+                    for (x, y) in safe {
+                        assert!(!open_with_cascade(&mut field, x, y));
+                    }
+                    continue;
+                }
+                let candidate = deductions.iter()
+                    .filter(|d| matches!(d.kind, DeductionKind::Candidate(_))).filter(closed)
+                    .find(|d| field.get_tile(d.x, d.y).is_some_and(|t| !t.collapsed))
+                    .map(|d| (d.x, d.y));
+                let Some((x, y)) = candidate else { break; };
+                field.collapse(x, y).unwrap();
+            }
+        }
+        assert!(checked > 500, "too few deductions checked: {}", checked);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::analysis::Difficulty;
+use crate::field::Field;
+use crate::tile::*;
+
+/// Текущее время в миллисекундах: в браузере из `Date`, иначе из системных часов.
+#[cfg(feature = "wasm")]
+pub fn now_ms() -> f64 {
+    return js_sys::Date::now();
+}
+
+/// Текущее время в миллисекундах: в браузере из `Date`, иначе из системных часов.
+#[cfg(not(feature = "wasm"))]
+pub fn now_ms() -> f64 {
+    let elapsed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    return elapsed.as_secs_f64() * 1000.0;
+}
+
+/// Счётчики действий игрока за партию. Отмена ходов их не откатывает.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GameStats {
+    pub clicks : u32,      // все клики по клеткам
+    pub opens : u32,       // клики лопатой, которые что-то открыли
+    pub flags : u32,       // поставлено обычных флажков
+    pub quant_flags : u32, // поставлено квантовых флажков
+    pub collapses : u32,   // измерения кнопкой коллапса, которые что-то изменили
+    pub hints : u32,
+    pub started_at : Option<f64>, // мс, первый клик
+    pub finished_at : Option<f64>, // мс, конец игры
+    pub paused_at : Option<f64>    // мс, начало текущей паузы
+}
+
+/// Итог партии для попапа и отправки боту.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameSummary {
+    pub finished : bool,
+    pub won : bool,
+    pub elapsed_ms : f64,
+    pub clicks : u32,
+    pub opens : u32,
+    pub flags : u32,
+    pub wrong_flags : u32,     // обычные флажки не на минах
+    pub quant_flags_used : u32,
+    pub quant_flags_left : u32,
+    pub probes_used : u32,
+    pub collapses : u32,
+    pub hints : u32,
+    pub complexity : u32,      // квантовый 3BV поля (см. `Analyzer`)
+    pub difficulty : Option<Difficulty>,
+    pub score : u32
+}
+
+/// Очки: 10 за единицу сложности, умноженные на долю открытых безопасных клеток и на точность
+/// (сложность / клики, не больше 1); при победе ещё по 5 за каждый оставшийся квантовый флажок.
+pub fn score(field : &Field, complexity : u32, clicks : u32, won : bool, quant_flags_left : u32) -> u32 {
+    let opened = field.tiles.iter().filter(|t| t.status == TileStatus::Opened).count() as f64;
+    let closed_safe = field.tiles.iter().filter(|t| t.status != TileStatus::Opened && t.prob != Prob::MINE).count() as f64;
+    let progress = if opened + closed_safe > 0.0 { opened / (opened + closed_safe) } else { 0.0 };
+    let accuracy = if clicks > 0 { (complexity as f64 / clicks as f64).min(1.0) } else { 0.0 };
+    let bonus = if won { quant_flags_left * 5 } else { 0 };
+    return (complexity as f64 * 10.0 * progress * accuracy).round() as u32 + bonus;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{GameConfig, GameEngine, ToolType};
+    use crate::misc::MiscMethods;
+    use crate::solver::index_to_coords;
+    use crate::tile::*;
+
+    #[test]
+    fn counters_skip_moves_without_changes() {
+        let config = GameConfig::builder(10, 10).seed(2).build().unwrap();
+        let mut engine = GameEngine::new();
+        engine.start_new_game(config).unwrap();
+        engine.tile_interact(5, 5).unwrap();
+        assert_eq!(engine.summary().unwrap().opens, 1);
+
+        // аккорд по числу без флажков вокруг ничего не открывает
+        let field = engine.field().unwrap();
+        let (x, y) = (0..field.tiles.len()).map(|i| index_to_coords(field, i)).find(|&(x, y)| {
+            return field.get_tile(x, y).unwrap().status == TileStatus::Opened && field.around_prob_sum(x, y).unwrap() != Prob::ZERO;
+        }).unwrap();
+        engine.tile_interact(x, y).unwrap();
+        let summary = engine.summary().unwrap();
+        assert_eq!((summary.clicks, summary.opens), (2, 1));
+
+        // коллапс без квантовых флажков ничего не меняет
+        engine.collapse_quant_flags().unwrap();
+        assert_eq!(engine.summary().unwrap().collapses, 0);
+
+        let field = engine.field().unwrap();
+        let (x, y) = (0..field.tiles.len()).map(|i| index_to_coords(field, i))
+            .find(|&(x, y)| field.get_tile(x, y).is_some_and(|t| t.status == TileStatus::None && t.mine_id.is_some() && !t.collapsed))
+            .unwrap();
+        engine.change_tool(ToolType::QuantFlag);
+        engine.tile_interact(x, y).unwrap();
+        engine.collapse_quant_flags().unwrap();
+        assert_eq!(engine.summary().unwrap().collapses, 1);
+    }
+}
@@ -1,20 +1,52 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-#[wasm_bindgen]
-#[derive(Debug, PartialEq, Clone, PartialOrd)]
-pub struct Prob(pub u8); // n / 12
+use crate::error::EngineError;
+use crate::generator::MAX_GROUP_SIZE;
+
+const fn gcd(a : u32, b : u32) -> u32 {
+    if b == 0 { return a; }
+    return gcd(b, a % b);
+}
+
+// НОК чисел 1..=n
+const fn lcm_up_to(n : usize) -> u32 {
+    let mut result = 1;
+    let mut i = 2;
+    while i <= n as u32 {
+        result = result / gcd(result, i) * i;
+        i += 1;
+    }
+    return result;
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd)]
+pub struct Prob(pub u32); // n / Prob::DENOMINATOR
 
 impl Prob {
-    pub fn new(value : u8) -> Prob {
+    /// Общий знаменатель: НОК всех допустимых размеров групп, так что 1/размер всегда целое.
+    pub const DENOMINATOR : u32 = lcm_up_to(MAX_GROUP_SIZE);
+    pub const ZERO : Prob = Prob(0);
+    pub const MINE : Prob = Prob(Prob::DENOMINATOR);
+
+    pub fn new(value : u32) -> Prob {
         return Prob(value);
     }
 
+    /// Сумма вероятностей не переполняется: в худшем случае она упирается в `u32::MAX`.
     pub fn add(&mut self, other : &Prob) {
-        self.0 += other.0;
+        self.0 = self.0.saturating_add(other.0);
+    }
+
+    /// Несократимая дробь `(числитель, знаменатель)`.
+    pub fn reduced(&self) -> (u32, u32) {
+        let divisor = gcd(self.0, Prob::DENOMINATOR);
+        return (self.0 / divisor, Prob::DENOMINATOR / divisor);
     }
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum TileStatus {
     None,       // 0
@@ -22,9 +54,8 @@ pub enum TileStatus {
     Flag,       // 2
     QuantFlag   // 3
 }
-/* 
 impl TileStatus {
-    fn to_u8(&self) -> u8 {
+    pub fn to_u8(&self) -> u8 {
         match self {
             TileStatus::Flag => return 2,
             TileStatus::QuantFlag => return 3,
@@ -33,69 +64,110 @@ impl TileStatus {
         }
     }
 
-    fn from_u8(value : u8) -> Result<TileStatus, &'static str> {
+    pub fn from_u8(value : u8) -> Result<TileStatus, EngineError> {
         match value {
             0 => return Ok(TileStatus::None),
             1 => return Ok(TileStatus::Opened),
             2 => return Ok(TileStatus::Flag),
             3 => return Ok(TileStatus::QuantFlag),
-            _ => return Err("")
+            _ => return Err(EngineError::CorruptedSave(format!("unknown tile status ({})", value)))
         }
-    } 
+    }
+}
+
+/// Что игрок видит на клетке, в том числе разметка флажков в конце игры (см. `GameEngine::board_view`).
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileView {
+    Closed,    // 0
+    Flag,      // 1
+    QuantFlag, // 2
+    Opened,    // 3, с суммой вероятностей вокруг
+    Mine,      // 4, открытая мина
+    RightFlag  // 5, флажок на мине после конца игры
+}
+
+/// Номер группы клеток. `u32::MAX` занят под «нет группы» в сохранении, поэтому групп не больше `GroupId::MAX + 1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GroupId(pub u32);
+
+impl GroupId {
+    pub const MAX : u32 = u32::MAX - 1;
+    const NONE_BYTES : u32 = u32::MAX;
+
+    /// Номер группы по её порядковому индексу; `None`, если индекс не помещается.
+    pub fn new(index : usize) -> Option<GroupId> {
+        return u32::try_from(index).ok().filter(|&id| id <= GroupId::MAX).map(GroupId);
+    }
+
+    pub fn index(&self) -> usize {
+        return self.0 as usize;
+    }
+}
+
+impl std::fmt::Display for GroupId {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}", self.0);
+    }
 }
-*/
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Tile {
     pub status: TileStatus,
     pub prob: Prob,
     pub collapsed: bool,
-    pub mine_id: i16
+    pub mine_id: Option<GroupId> // `None` - клетка вне групп
+}
+
+impl Default for Tile {
+    fn default() -> Self {
+        return Tile::new();
+    }
 }
 
 impl Tile {
     pub const fn new() -> Self {
         return Self {
-            prob: Prob(0),
+            prob: Prob::ZERO,
             collapsed: false,
-            mine_id: -1,
+            mine_id: None,
             status: TileStatus::None
         };
     }
 
-    pub fn from_mine(mine_id : i16, probability : Prob) -> Self {
+    pub fn from_mine(mine_id : GroupId, probability : Prob) -> Self {
         return Self {
             prob: probability,
-            mine_id: mine_id,
+            mine_id: Some(mine_id),
             status: TileStatus::None,
             collapsed: false
         };
     }
-    /* 
-    pub fn to_bytes(&self) -> [u8; 3] {
-        let mut result : [u8; 3] = [0; 3];
-        result[0] = self.status.to_u8() + 4 * self.collapsed as u8;
-        result[1] = self.group_id as u8;
-        result[2] = self.prob.0;
-        return result;
+
+    pub const BYTES : usize = 7;
+
+    // [статус + 4 * collapsed, mine_id (u32, LE, `u32::MAX` - нет группы), prob (u16, LE)]
+    pub fn to_bytes(&self) -> [u8; Tile::BYTES] {
+        let mine_id = self.mine_id.map_or(GroupId::NONE_BYTES, |group| group.0).to_le_bytes();
+        let prob = (self.prob.0 as u16).to_le_bytes();
+        return [self.status.to_u8() + 4 * self.collapsed as u8, mine_id[0], mine_id[1], mine_id[2], mine_id[3], prob[0], prob[1]];
     }
 
-    pub fn from_bytes(bytes : &[u8]) -> Result<Tile, &'static str> {
-        if bytes.len() == 3 {
-            if let Ok(status) = TileStatus::from_u8(bytes[0] % 4) {
-                let collapsed = bytes[0] / 4 > 0;
-                let group_id = bytes[1] as i8;
-                let prob = Prob(bytes[2]);
-                return Ok(Tile {
-                    collapsed,
-                    group_id,
-                    prob,
-                    status
-                });
-            }
-            return Err("Tile status isn't parsed.");
+    pub fn from_bytes(bytes : &[u8]) -> Result<Tile, EngineError> {
+        if bytes.len() != Tile::BYTES {
+            return Err(EngineError::CorruptedSave(format!("tile data length isn't equal {}", Tile::BYTES)));
+        }
+        if bytes[0] >= 8 {
+            return Err(EngineError::CorruptedSave(format!("invalid tile header ({})", bytes[0])));
+        }
+        let status = TileStatus::from_u8(bytes[0] % 4)?;
+        let collapsed = bytes[0] / 4 > 0;
+        let mine_id = u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+        let mine_id = (mine_id != GroupId::NONE_BYTES).then_some(GroupId(mine_id));
+        let prob = Prob(u16::from_le_bytes([bytes[5], bytes[6]]) as u32);
+        if prob > Prob::MINE {
+            return Err(EngineError::CorruptedSave(format!("invalid probability ({}/{})", prob.0, Prob::DENOMINATOR)));
         }
-        return Err("Data length isn't equal 3");
+        return Ok(Tile { status, prob, collapsed, mine_id });
     }
-    */
 }
\ No newline at end of file
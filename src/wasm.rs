@@ -0,0 +1,548 @@
+use js_sys::{Array, Object, Reflect, Uint16Array};
+use wasm_bindgen::prelude::*;
+
+use crate::bytes::Byter;
+use crate::engine::{GameConfig, GameEngine, GameState, ToolType};
+use crate::error::EngineError;
+use crate::field::{Tiling, Topology};
+use crate::hint::{HintAction, HintReason};
+use crate::replay::{Replay, ReplayPlayer};
+use crate::solver::DeductionKind;
+use crate::tile::{Prob, TileStatus};
+
+fn coords_object(x : i32, y : i32) -> Object {
+    let obj = Object::new();
+    Reflect::set(&obj, &"x".into(), &JsValue::from(x)).unwrap();
+    Reflect::set(&obj, &"y".into(), &JsValue::from(y)).unwrap();
+    return obj;
+}
+
+fn fraction_object(prob : &Prob) -> Object {
+    let (num, den) = prob.reduced();
+    let obj = Object::new();
+    Reflect::set(&obj, &"num".into(), &JsValue::from(num)).unwrap();
+    Reflect::set(&obj, &"den".into(), &JsValue::from(den)).unwrap();
+    return obj;
+}
+
+/// Ошибка для JS: `Error` с сообщением, стабильным полем `code` и координатами клетки или номером группы, если они есть.
+impl From<EngineError> for JsValue {
+    fn from(error : EngineError) -> JsValue {
+        let js_error = js_sys::Error::new(&error.to_string());
+        Reflect::set(&js_error, &"code".into(), &error.code().into()).unwrap();
+        if let Some((x, y)) = error.coords() {
+            Reflect::set(&js_error, &"x".into(), &JsValue::from(x)).unwrap();
+            Reflect::set(&js_error, &"y".into(), &JsValue::from(y)).unwrap();
+        }
+        if let Some(group) = error.group() {
+            Reflect::set(&js_error, &"group".into(), &JsValue::from(group.0)).unwrap();
+        }
+        return js_error.into();
+    }
+}
+
+/// JS-обёртка над `GameConfig`: размеры, доли групп и кандидатов и опции игры.
+#[wasm_bindgen(js_name = "GameConfig")]
+pub struct WasmConfig {
+    config : GameConfig
+}
+
+#[wasm_bindgen(js_class = "GameConfig")]
+impl WasmConfig {
+    #[wasm_bindgen(constructor)]
+    pub fn new(width : u32, height : u32, groups : f64, candidates : f64) -> WasmConfig {
+        return WasmConfig { config: GameConfig::new(width, height, groups, candidates) };
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn seed(&self) -> Option<u64> {
+        return self.config.seed;
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_seed(&mut self, seed : Option<u64>) {
+        self.config.seed = seed;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn undo(&self) -> bool {
+        return self.config.undo;
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_undo(&mut self, undo : bool) {
+        self.config.undo = undo;
+    }
+
+    #[wasm_bindgen(getter, js_name = "noGuess")]
+    pub fn no_guess(&self) -> bool {
+        return self.config.no_guess;
+    }
+
+    #[wasm_bindgen(setter, js_name = "noGuess")]
+    pub fn set_no_guess(&mut self, no_guess : bool) {
+        self.config.no_guess = no_guess;
+    }
+
+    #[wasm_bindgen(getter, js_name = "hintCost")]
+    pub fn hint_cost(&self) -> u32 {
+        return self.config.hint_cost;
+    }
+
+    #[wasm_bindgen(setter, js_name = "hintCost")]
+    pub fn set_hint_cost(&mut self, cost : u32) {
+        self.config.hint_cost = cost;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn topology(&self) -> Topology {
+        return self.config.topology;
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_topology(&mut self, topology : Topology) {
+        self.config.topology = topology;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn tiling(&self) -> Tiling {
+        return self.config.tiling;
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_tiling(&mut self, tiling : Tiling) {
+        self.config.tiling = tiling;
+    }
+
+    #[wasm_bindgen(getter, js_name = "minGroupSize")]
+    pub fn min_group_size(&self) -> u32 {
+        return self.config.min_group_size;
+    }
+
+    #[wasm_bindgen(setter, js_name = "minGroupSize")]
+    pub fn set_min_group_size(&mut self, size : u32) {
+        self.config.min_group_size = size;
+    }
+
+    #[wasm_bindgen(getter, js_name = "maxGroupSize")]
+    pub fn max_group_size(&self) -> u32 {
+        return self.config.max_group_size;
+    }
+
+    #[wasm_bindgen(setter, js_name = "maxGroupSize")]
+    pub fn set_max_group_size(&mut self, size : u32) {
+        self.config.max_group_size = size;
+    }
+
+    #[wasm_bindgen(getter, js_name = "groupMines")]
+    pub fn group_mines(&self) -> u32 {
+        return self.config.group_mines;
+    }
+
+    #[wasm_bindgen(setter, js_name = "groupMines")]
+    pub fn set_group_mines(&mut self, mines : u32) {
+        self.config.group_mines = mines;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn entangled(&self) -> f64 {
+        return self.config.entangled;
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_entangled(&mut self, entangled : f64) {
+        self.config.entangled = entangled;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn probes(&self) -> u32 {
+        return self.config.probes;
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_probes(&mut self, probes : u32) {
+        self.config.probes = probes;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn huge(&self) -> bool {
+        return self.config.huge;
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_huge(&mut self, huge : bool) {
+        self.config.huge = huge;
+    }
+
+    #[wasm_bindgen(getter, js_name = "generationAttempts")]
+    pub fn generation_attempts(&self) -> u32 {
+        return self.config.generation_attempts;
+    }
+
+    #[wasm_bindgen(setter, js_name = "generationAttempts")]
+    pub fn set_generation_attempts(&mut self, attempts : u32) {
+        self.config.generation_attempts = attempts;
+    }
+
+    /// Проверка по правилам движка (`GameConfig::validate`): бросает ошибку с кодом `invalidConfig`.
+    pub fn validate(&self) -> Result<(), JsValue> {
+        return Ok(self.config.validate()?);
+    }
+
+    /// `{groups, candidates, quantFlags}` для игры с этим конфигом; бросает ошибку, если конфиг неверный.
+    #[wasm_bindgen(js_name = "getCounts")]
+    pub fn counts(&self) -> Result<JsValue, JsValue> {
+        let counts = self.config.counts()?;
+        let obj = Object::new();
+        Reflect::set(&obj, &"groups".into(), &JsValue::from(counts.groups as u32)).unwrap();
+        Reflect::set(&obj, &"candidates".into(), &JsValue::from(counts.candidates as u32)).unwrap();
+        Reflect::set(&obj, &"quantFlags".into(), &JsValue::from(counts.quant_flags as u32)).unwrap();
+        return Ok(obj.into());
+    }
+}
+
+/// JS-обёртка над `GameEngine`: переводит ошибки в JS-объекты с `code` и изменения поля в JS-объекты.
+#[wasm_bindgen(js_name = "GameEngine")]
+pub struct WasmEngine {
+    engine : GameEngine
+}
+
+impl Default for WasmEngine {
+    fn default() -> Self {
+        return WasmEngine::new();
+    }
+}
+
+#[wasm_bindgen(js_class = "GameEngine")]
+impl WasmEngine {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmEngine {
+        return WasmEngine { engine: GameEngine::new() };
+    }
+
+    // Геттеры
+    /// Стадия партии: `{kind: "notStarted" | "playing" | "won" | "lost" | "paused", x?, y?}`,
+    /// у проигрыша `x, y` - клетка взрыва.
+    #[wasm_bindgen(getter, js_name = "getState")]
+    pub fn state(&self) -> Object {
+        let (kind, coords) = match self.engine.state() {
+            GameState::NotStarted => ("notStarted", None),
+            GameState::Playing => ("playing", None),
+            GameState::Won => ("won", None),
+            GameState::Lost { x, y } => ("lost", Some((x, y))),
+            GameState::Paused => ("paused", None)
+        };
+        let obj = match coords {
+            Some((x, y)) => coords_object(x, y),
+            None => Object::new()
+        };
+        Reflect::set(&obj, &"kind".into(), &kind.into()).unwrap();
+        return obj;
+    }
+
+    #[wasm_bindgen(getter, js_name = "isGameOver")]
+    pub fn is_game_over(&self) -> bool {
+        return self.engine.is_game_over();
+    }
+
+    #[wasm_bindgen(getter, js_name = "getQuantFlagCount")]
+    pub fn quant_flags(&self) -> usize {
+        return self.engine.quant_flags();
+    }
+
+    #[wasm_bindgen(getter, js_name = "getProbeCount")]
+    pub fn probes(&self) -> usize {
+        return self.engine.probes();
+    }
+
+    #[wasm_bindgen(getter, js_name = "getCurrentTool")]
+    pub fn current_tool(&self) -> ToolType {
+        return self.engine.current_tool();
+    }
+
+    #[wasm_bindgen(getter, js_name = "getSeed")]
+    pub fn seed(&self) -> Result<u64, JsValue> {
+        return Ok(self.engine.seed()?);
+    }
+
+    #[wasm_bindgen(getter, js_name = "hasFieldNow")]
+    pub fn has_field_now(&self) -> bool {
+        return self.engine.has_field_now();
+    }
+
+    #[wasm_bindgen(getter, js_name = "fieldWidth")]
+    pub fn width(&self) -> Result<u32, JsValue> {
+        return Ok(self.engine.width()?);
+    }
+
+    #[wasm_bindgen(getter, js_name = "fieldHeight")]
+    pub fn height(&self) -> Result<u32, JsValue> {
+        return Ok(self.engine.height()?);
+    }
+
+    #[wasm_bindgen(getter, js_name = "canUndo")]
+    pub fn can_undo(&self) -> bool {
+        return self.engine.can_undo();
+    }
+
+    #[wasm_bindgen(getter, js_name = "canRedo")]
+    pub fn can_redo(&self) -> bool {
+        return self.engine.can_redo();
+    }
+
+    /// Изменённые клетки: `[{x, y, flags}]`, `flags` - причины (1 - статус, 2 - число вокруг,
+    /// 4 - коллапс, 8 - мина, 16 - разметка флажка в конце игры). Для отрисовки быстрее `getChangesView`.
+    #[wasm_bindgen(getter, js_name = "fieldChanges")]
+    pub fn field_changes(&self) -> Array {
+        return Array::from_iter(self.engine.field_changes().iter().map(|change| {
+            let obj = coords_object(change.x, change.y);
+            Reflect::set(&obj, &"flags".into(), &JsValue::from(change.flags)).unwrap();
+            return obj;
+        }));
+    }
+
+    /// Всё поле одним массивом: `[вид (TileView), числитель, знаменатель]` на клетку, строка за строкой.
+    #[wasm_bindgen(js_name = "getBoardView")]
+    pub fn board_view(&self) -> Result<Uint16Array, JsValue> {
+        return Ok(Uint16Array::from(&self.engine.board_view()?[..]));
+    }
+
+    /// Изменённые клетки одним массивом: `[x, y, причины (см. fieldChanges), вид (TileView), числитель, знаменатель]` на клетку.
+    #[wasm_bindgen(js_name = "getChangesView")]
+    pub fn changes_view(&self) -> Result<Uint16Array, JsValue> {
+        return Ok(Uint16Array::from(&self.engine.changes_view()?[..]));
+    }
+
+    // геттеры клеток
+    #[wasm_bindgen(js_name = "isTileMine")]
+    pub fn is_tile_mine(&self, x : i32, y : i32) -> Result<bool, JsValue> {
+        return Ok(self.engine.is_tile_mine(x, y)?);
+    }
+
+    #[wasm_bindgen(js_name = "getTileStatus")]
+    pub fn get_tile_status(&self, x : i32, y : i32) -> Result<TileStatus, JsValue> {
+        return Ok(self.engine.get_tile_status(x, y)?);
+    }
+
+    /// Сумма вероятностей вокруг клетки несократимой дробью `{num, den}`.
+    #[wasm_bindgen(js_name = "getProbabilityAroundTile")]
+    pub fn get_prob_around(&self, x : i32, y : i32) -> Result<Object, JsValue> {
+        return Ok(fraction_object(&self.engine.get_prob_around(x, y)?));
+    }
+
+    /// Запутанный партнёр измеренной группы клетки: `{kind: Entanglement, tiles: [{x, y}]}` или `null`.
+    #[wasm_bindgen(js_name = "getEntangledPartner")]
+    pub fn entangled_partner(&self, x : i32, y : i32) -> Result<JsValue, JsValue> {
+        let Some(partner) = self.engine.entangled_partner(x, y)? else { return Ok(JsValue::NULL); };
+        let obj = Object::new();
+        Reflect::set(&obj, &"kind".into(), &JsValue::from(partner.kind)).unwrap();
+        let tiles = Array::from_iter(partner.tiles.iter().map(|(x, y)| coords_object(*x, *y)));
+        Reflect::set(&obj, &"tiles".into(), &tiles).unwrap();
+        return Ok(obj.into());
+    }
+
+    /// Раскладка клеток: `[{x, y, cx, cy, shape}]`, центры в единицах стороны клетки (см. `Tiling::center`).
+    #[wasm_bindgen(js_name = "getLayout")]
+    pub fn layout(&self) -> Result<Array, JsValue> {
+        let width = self.engine.width()?;
+        return Ok(Array::from_iter(self.engine.layout()?.into_iter().enumerate().map(|(i, (cx, cy, shape))| {
+            let obj = coords_object((i as u32 % width) as i32, (i as u32 / width) as i32);
+            Reflect::set(&obj, &"cx".into(), &JsValue::from(cx)).unwrap();
+            Reflect::set(&obj, &"cy".into(), &JsValue::from(cy)).unwrap();
+            Reflect::set(&obj, &"shape".into(), &JsValue::from(shape)).unwrap();
+            return obj;
+        })));
+    }
+
+    /// Выводы солвера: `{x, y, kind: "safe" | "mine" | "candidate", prob?, reasons: [{x, y}]}`.
+    #[wasm_bindgen(js_name = "getDeductions")]
+    pub fn deductions(&self) -> Result<Array, JsValue> {
+        return Ok(Array::from_iter(self.engine.deductions()?.iter().map(|deduction| {
+            let obj = coords_object(deduction.x, deduction.y);
+            let (kind, prob) = match &deduction.kind {
+                DeductionKind::Safe => ("safe", None),
+                DeductionKind::Mine => ("mine", None),
+                DeductionKind::Candidate(prob) => ("candidate", prob.as_ref().map(fraction_object))
+            };
+            Reflect::set(&obj, &"kind".into(), &kind.into()).unwrap();
+            if let Some(prob) = prob {
+                Reflect::set(&obj, &"prob".into(), &prob).unwrap();
+            }
+            let reasons = Array::from_iter(deduction.reasons.iter().map(|(x, y)| coords_object(*x, *y)));
+            Reflect::set(&obj, &"reasons".into(), &reasons).unwrap();
+            return obj;
+        })));
+    }
+
+    /// Подсказка: `{x, y, tool: "shovel" | "flag" | "quantFlag" | "collapse",
+    /// reason: "firstClick" | "forcedSafe" | "forcedMine" | "measure" | "collapsePending" | "lowestRisk",
+    /// neighbours?: [{x, y}], odds?}`.
+    #[wasm_bindgen(js_name = "getHint")]
+    pub fn hint(&mut self) -> Result<Object, JsValue> {
+        let hint = self.engine.hint()?;
+        let obj = coords_object(hint.x, hint.y);
+        let tool = match hint.action {
+            HintAction::Open => "shovel",
+            HintAction::Flag => "flag",
+            HintAction::QuantFlag => "quantFlag",
+            HintAction::Collapse => "collapse"
+        };
+        let (reason, neighbours, odds) = match &hint.reason {
+            HintReason::FirstClick => ("firstClick", None, None),
+            HintReason::ForcedSafe { neighbours } => ("forcedSafe", Some(neighbours), None),
+            HintReason::ForcedMine { neighbours } => ("forcedMine", Some(neighbours), None),
+            HintReason::Measure { neighbours } => ("measure", Some(neighbours), None),
+            HintReason::PendingQuantFlags => ("collapsePending", None, None),
+            HintReason::LowestRisk { odds } => ("lowestRisk", None, Some(*odds))
+        };
+        Reflect::set(&obj, &"tool".into(), &tool.into()).unwrap();
+        Reflect::set(&obj, &"reason".into(), &reason.into()).unwrap();
+        if let Some(neighbours) = neighbours {
+            let neighbours = Array::from_iter(neighbours.iter().map(|(x, y)| coords_object(*x, *y)));
+            Reflect::set(&obj, &"neighbours".into(), &neighbours).unwrap();
+        }
+        if let Some(odds) = odds {
+            Reflect::set(&obj, &"odds".into(), &JsValue::from(odds)).unwrap();
+        }
+        return Ok(obj);
+    }
+
+    /// Разбор поля: `{clicks, openings, superposition, guesses, difficulty: Difficulty}` или `null` до первого клика.
+    /// На полях больше `SIMULATION_TILE_LIMIT` клеток и если симуляции не хватило `SIMULATION_STEPS` шагов, `guesses` и `difficulty` - `null`.
+    #[wasm_bindgen(js_name = "getAnalysis")]
+    pub fn analysis(&self) -> JsValue {
+        let Some(analysis) = self.engine.analysis() else { return JsValue::NULL; };
+        let obj = Object::new();
+        Reflect::set(&obj, &"clicks".into(), &JsValue::from(analysis.clicks)).unwrap();
+        Reflect::set(&obj, &"openings".into(), &JsValue::from(analysis.openings)).unwrap();
+        Reflect::set(&obj, &"superposition".into(), &JsValue::from(analysis.superposition)).unwrap();
+        Reflect::set(&obj, &"guesses".into(), &analysis.guesses.map_or(JsValue::NULL, JsValue::from)).unwrap();
+        Reflect::set(&obj, &"difficulty".into(), &analysis.difficulty().map_or(JsValue::NULL, JsValue::from)).unwrap();
+        return obj.into();
+    }
+
+    /// Итог партии: `{finished, won, elapsedMs, clicks, opens, flags, wrongFlags, quantFlagsUsed,
+    /// quantFlagsLeft, probesUsed, collapses, hints, complexity, difficulty: Difficulty | null, score}`.
+    #[wasm_bindgen(js_name = "getSummary")]
+    pub fn summary(&self) -> Result<Object, JsValue> {
+        let summary = self.engine.summary()?;
+        let obj = Object::new();
+        let fields : [(&str, JsValue); 15] = [
+            ("finished", summary.finished.into()),
+            ("won", summary.won.into()),
+            ("elapsedMs", summary.elapsed_ms.into()),
+            ("clicks", summary.clicks.into()),
+            ("opens", summary.opens.into()),
+            ("flags", summary.flags.into()),
+            ("wrongFlags", summary.wrong_flags.into()),
+            ("quantFlagsUsed", summary.quant_flags_used.into()),
+            ("quantFlagsLeft", summary.quant_flags_left.into()),
+            ("probesUsed", summary.probes_used.into()),
+            ("collapses", summary.collapses.into()),
+            ("hints", summary.hints.into()),
+            ("complexity", summary.complexity.into()),
+            ("difficulty", summary.difficulty.map_or(JsValue::NULL, JsValue::from)),
+            ("score", summary.score.into())
+        ];
+        for (key, value) in fields {
+            Reflect::set(&obj, &key.into(), &value).unwrap();
+        }
+        return Ok(obj);
+    }
+
+    /// Вероятность мины по клеткам (строка за строкой) с точки зрения игрока, `NaN` для открытых клеток.
+    #[wasm_bindgen(js_name = "getMineOdds")]
+    pub fn mine_odds(&self) -> Result<Vec<f64>, JsValue> {
+        return Ok(self.engine.mine_odds()?.into_iter().map(|odds| odds.unwrap_or(f64::NAN)).collect());
+    }
+
+    // экспортируемые методы для привязки
+    #[wasm_bindgen(js_name = "startNewGame")]
+    pub fn start_new_game(&mut self, config : &WasmConfig) -> Result<(), JsValue> {
+        return Ok(self.engine.start_new_game(config.config.clone())?);
+    }
+
+    #[wasm_bindgen(js_name = "handleTileInteraction")]
+    pub fn tile_interact(&mut self, x : i32, y : i32) -> Result<(), JsValue> {
+        return Ok(self.engine.tile_interact(x, y)?);
+    }
+
+    #[wasm_bindgen(js_name = "collapseQuantFlags")]
+    pub fn collapse_quant_flags(&mut self) -> Result<(), JsValue> {
+        return Ok(self.engine.collapse_quant_flags()?);
+    }
+
+    #[wasm_bindgen(js_name = "changeTool")]
+    pub fn change_tool(&mut self, tool : ToolType) {
+        self.engine.change_tool(tool);
+    }
+
+    pub fn undo(&mut self) -> Result<bool, JsValue> {
+        return Ok(self.engine.undo()?);
+    }
+
+    pub fn redo(&mut self) -> Result<bool, JsValue> {
+        return Ok(self.engine.redo()?);
+    }
+
+    pub fn pause(&mut self) -> Result<(), JsValue> {
+        return Ok(self.engine.pause()?);
+    }
+
+    pub fn resume(&mut self) -> Result<(), JsValue> {
+        return Ok(self.engine.resume()?);
+    }
+
+    #[wasm_bindgen(js_name = "saveGame")]
+    pub fn save_game(&self) -> Vec<u8> {
+        return self.engine.save_game();
+    }
+
+    #[wasm_bindgen(js_name = "loadGame")]
+    pub fn load_game(&mut self, bytes : &[u8]) -> Result<(), JsValue> {
+        return Ok(self.engine.load_game(bytes)?);
+    }
+
+    #[wasm_bindgen(js_name = "exportReplay")]
+    pub fn export_replay(&self) -> Option<Vec<u8>> {
+        return self.engine.replay().map(|replay| replay.to_bytes());
+    }
+
+    #[wasm_bindgen(js_name = "exportReplayText")]
+    pub fn export_replay_text(&self) -> Option<String> {
+        return self.engine.replay().map(|replay| replay.to_text());
+    }
+}
+
+/// JS-обёртка над `ReplayPlayer`. `seek` отдаёт копию движка, которую можно рисовать как обычную игру.
+#[wasm_bindgen(js_name = "ReplayPlayer")]
+pub struct WasmReplayPlayer {
+    player : ReplayPlayer
+}
+
+#[wasm_bindgen(js_class = "ReplayPlayer")]
+impl WasmReplayPlayer {
+    #[wasm_bindgen(js_name = "fromBytes")]
+    pub fn from_bytes(bytes : &[u8]) -> Result<WasmReplayPlayer, JsValue> {
+        return Ok(WasmReplayPlayer { player: ReplayPlayer::new(Replay::from_bytes(bytes)?)? });
+    }
+
+    #[wasm_bindgen(js_name = "fromText")]
+    pub fn from_text(text : &str) -> Result<WasmReplayPlayer, JsValue> {
+        return Ok(WasmReplayPlayer { player: ReplayPlayer::new(Replay::from_text(text)?)? });
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        return self.player.len();
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn position(&self) -> usize {
+        return self.player.position();
+    }
+
+    pub fn seek(&mut self, index : usize) -> Result<WasmEngine, JsValue> {
+        return Ok(WasmEngine { engine: self.player.seek(index)?.clone() });
+    }
+}
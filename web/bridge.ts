@@ -1,16 +1,14 @@
 //import { debugMessage, GameConfig } from './static';
-import init, { GameEngine, TileStatus } from '../pkg/quantswepeer.js';
+import init, { GameEngine, GameConfig as EngineConfig, TileView, Tiling, Topology } from '../pkg/quantswepeer.js';
 import { DOMManager } from './dom';
 //import { GUI } from "./gui";
 
 import { FieldManager } from './field';
-import { GameConfig } from './types';
+import { ChangeFlag, GameConfig, GameSummary } from './types';
+import { errorText } from './lang';
 
-interface Position {
-    x : number, y : number
-};
-
-const gcd = (a : number, b : number) => {if (!b) {return a;}return gcd(b, a % b)};
+const SAVE_KEY = 'savedGame';
+const SAVE_CHUNK = 0x8000;
 
 export class WasmHook {
     private engine : GameEngine;
@@ -21,96 +19,153 @@ export class WasmHook {
     constructor() {
         this.engine = new GameEngine();
         this.field = new FieldManager((x : number, y : number) => {
-            this.engine.handleTileInteraction(x, y);
+            if (this.engine.isGameOver) return;
+            try {
+                this.engine.handleTileInteraction(x, y);
+            } catch (e) {
+                console.warn(`Move is rejected: ${e}`); // например, флажок до первого клика
+                return;
+            }
             this.renderField();
+            if (this.engine.isGameOver) this.finishGame();
         });
 
         const onCollapse = () => {
-            console.log("Collapsing!!!");
-            this.engine.collapseQuantFlags();
+            try {
+                this.engine.collapseQuantFlags();
+            } catch (e) {
+                console.warn(`Collapse is rejected: ${e}`); // например, до первого клика или на паузе
+                return;
+            }
             this.renderField();
         };
 
-        const onNewGame = config => {
-            config = this.validateConfig(config);
-            this.engine.startNewGame(
+        // правила конфига (размеры, доли групп и кандидатов, щупы) проверяет движок
+        const onNewGame = (config: GameConfig) => {
+            const engineConfig = new EngineConfig(
                 config.width, 
                 config.height, 
                 config.groups / 100, 
                 config.candidates / 100
             );
-            this.field.createBoard(config.width, config.height);
+            engineConfig.topology = config.topology ?? Topology.Rectangle;
+            engineConfig.tiling = config.tiling ?? Tiling.Square;
+            if (config.minGroupSize !== undefined) engineConfig.minGroupSize = config.minGroupSize;
+            if (config.maxGroupSize !== undefined) engineConfig.maxGroupSize = config.maxGroupSize;
+            if (config.groupMines !== undefined) engineConfig.groupMines = config.groupMines;
+            if (config.entangled !== undefined) engineConfig.entangled = config.entangled;
+            if (config.probes !== undefined) engineConfig.probes = config.probes;
+            try {
+                engineConfig.validate();
+            } catch (e) {
+                $('#config-error').text(errorText(e.code));
+                return;
+            }
+            $('#config-error').text('');
+            this.engine.startNewGame(engineConfig);
+            this.field.createBoard(config.width, config.height, this.engine.getLayout());
             this.dom.popupManager.closePopup();
             this.renderField();
         }
 
         this.dom = new DOMManager(onCollapse, tool => this.engine.changeTool(tool), onNewGame);
-        this.dom.popupManager.showNewGamePopup();
-    }
-    
-    private validateConfig(config: GameConfig): GameConfig {
-        const defaults: GameConfig = {
-            width: 10,      // стандартное значение ширины
-            height: 10,     // стандартное значение высоты
-            groups: 10,      // стандартное количество групп
-            candidates: 20   // стандартное количество кандидатов
-        };
-
-        const result: GameConfig = { ...defaults, ...config };
+        if (!this.restoreGame()) this.dom.popupManager.showNewGamePopup();
+
+        // таймер не идёт, пока Mini App свёрнут
+        $(document).on('visibilitychange', () => {
+            const state = this.engine.getState.kind;
+            if (document.hidden && state === 'playing') this.engine.pause();
+            else if (!document.hidden && state === 'paused') this.engine.resume();
+            else return;
+            this.saveGame();
+        });
 
-        // Проверка и корректировка числовых значений
-        result.width = Math.min(Math.max(Math.round(result.width || defaults.width), 5), 30);
-        result.height = Math.min(Math.max(Math.round(result.height || defaults.height), 5), 30);
-        result.groups = Math.min(Math.max(result.groups || defaults.groups, 1), 100);
+        $(document).on('keydown', e => {
+            if (!e.ctrlKey || !this.engine.hasFieldNow) return;
+            if (e.key === 'z' && this.engine.canUndo) this.engine.undo();
+            else if (e.key === 'y' && this.engine.canRedo) this.engine.redo();
+            else return;
+            this.renderField();
+        });
+    }
 
-        const minCandidates = result.groups;
-        const maxCandidates = Math.min(result.groups * 4, 100);
-        result.candidates = Math.min(
-            Math.max(result.candidates || defaults.candidates, minCandidates),
-            maxCandidates
-        );
+    // итог партии: попап и отправка боту, если игра открыта из Telegram
+    private finishGame(): void {
+        const summary: GameSummary = this.engine.getSummary();
+        this.dom.popupManager.showSummaryPopup(summary);
+        const webApp = (window as any).Telegram?.WebApp;
+        if (webApp?.sendData) {
+            try {
+                webApp.sendData(JSON.stringify(summary));
+            } catch (e) {
+                console.warn(`Summary isn't sent: ${e}`);
+            }
+        }
+    }
 
-        return result;
+    private saveGame(): void {
+        const bytes = this.engine.saveGame();
+        // по частям: разворот всего сохранения в аргументы переполняет стек на огромных полях
+        let binary = '';
+        for (let i = 0; i < bytes.length; i += SAVE_CHUNK) {
+            binary += String.fromCharCode(...bytes.subarray(i, i + SAVE_CHUNK));
+        }
+        localStorage.setItem(SAVE_KEY, btoa(binary));
     }
 
+    private restoreGame(): boolean {
+        const saved = localStorage.getItem(SAVE_KEY);
+        if (!saved) return false;
+        try {
+            this.engine.loadGame(Uint8Array.from(atob(saved), c => c.charCodeAt(0)));
+        } catch (e) {
+            console.warn(`Saved game is ignored: ${e}`);
+            localStorage.removeItem(SAVE_KEY);
+            return false;
+        }
+        if (!this.engine.hasFieldNow) return false;
+        if (this.engine.getState.kind === 'paused') this.engine.resume();
+        this.field.createBoard(this.engine.fieldWidth, this.engine.fieldHeight, this.engine.getLayout());
+        this.dom.setActiveTool(this.engine.getCurrentTool);
+        this.renderField();
+        return true;
+    }
+    
     private renderField(): void {
         if (!this.engine.hasFieldNow) return;
 
         this.dom.setQuantFlags(this.engine.getQuantFlagCount);
-
-        for (const coord of this.engine.fieldChanges.filter(p => this.posInsideBounds(p))) {
-            const x = coord.x;
-            const y = coord.y;
+        this.dom.setProbes(this.engine.getProbeCount);
+
+        // [x, y, причины, вид, числитель, знаменатель] на каждую изменённую клетку
+        const changes = this.engine.getChangesView();
+        for (let i = 0; i < changes.length; i += 6) {
+            const [x, y, flags, view, num, den] = changes.subarray(i, i + 6);
+            // коллапс закрытой клетки игроку не виден
+            if (!(flags & (ChangeFlag.Status | ChangeFlag.Sum | ChangeFlag.Marked))) continue;
             this.field.resetTile(x, y);
-            switch (this.engine.getTileStatus(x, y)) {
-                case TileStatus.None:
+            switch (view) {
+                case TileView.Closed:
                     this.field.setTileClosed(x, y);
                     break;
-                case TileStatus.Flag: 
-                    if (this.engine.isGameOver && this.engine.isTileMine(x, y)) this.field.setTileRightFlag(x, y);
-                    else this.field.setTileFlag(x, y);
+                case TileView.Flag:
+                    this.field.setTileFlag(x, y);
                     break;
-                case TileStatus.QuantFlag:
+                case TileView.RightFlag:
+                    this.field.setTileRightFlag(x, y);
+                    break;
+                case TileView.QuantFlag:
                     this.field.setTileQuantFlag(x, y);
                     break;
-                case TileStatus.Opened:
-                    if (this.engine.isTileMine(x, y)) this.field.setTileMine(x, y);
-                    else {
-                        const frac = this.reduceFrac(this.engine.getProbabilityAroundTile(x, y));
-                        this.field.setTileOpened(x, y, frac.num, frac.den);
-                    };
+                case TileView.Mine:
+                    this.field.setTileMine(x, y);
+                    break;
+                case TileView.Opened:
+                    this.field.setTileOpened(x, y, num, den);
                     break;
             }
         }
-    }
-
-    private reduceFrac(num : number, den : number = 12): {num : number, den : number} {
-        const x = gcd(num, den);
-        return {num : num / x, den : den / x};
-    }
-
-    private posInsideBounds(pos: Position): boolean {
-        return pos.x >= 0 && pos.x < this.engine.fieldWidth && pos.y >= 0 && pos.y < this.engine.fieldHeight;
+        this.saveGame();
     }
 }
 
@@ -7,10 +7,12 @@ export class DOMManager {
         [ToolType.Shovel]: '#tool-shovel',
         [ToolType.SimpleFlag]: '#classic-flag',
         [ToolType.QuantFlag]: '#quant-flag',
+        [ToolType.Probe]: '#probe',
     };
 
     private readonly tools: Record<ToolType, JQuery<HTMLElement>>;
     private readonly quantFlags = $('#quant-flag-count');
+    private readonly probes = $('#probe-count');
     private readonly links = $('#label');
     public readonly popupManager = new PopupManager();
 
@@ -32,6 +34,10 @@ export class DOMManager {
         this.quantFlags.text(value.toString());
     }
 
+    public setProbes(value: number): void {
+        this.probes.text(value.toString());
+    }
+
     public readGameConfig(): GameConfig {
         const getNum = (id: string) => Number($(`#${id}`).val());
         return {
@@ -39,6 +45,9 @@ export class DOMManager {
             height: getNum('height'),
             groups: getNum('groups'),
             candidates: getNum('candidates'),
+            probes: getNum('probes'),
+            topology: getNum('topology'),
+            tiling: getNum('tiling'),
         };
     }
 
@@ -67,9 +76,13 @@ export class DOMManager {
 
     private handleToolChange(tool: ToolType): void {
         console.log(`Changing tool to ${ToolType[tool]}`);
+        this.setActiveTool(tool);
+        this.onToolChanging(tool);
+    }
+
+    public setActiveTool(tool: ToolType): void {
         Object.values(this.tools).forEach(t => t.removeClass('active'));
         this.tools[tool].addClass('active');
-        this.onToolChanging(tool);
     }
 
     private handleGameStart(): void {
@@ -1,3 +1,18 @@
+import { TileShape } from '../pkg/quantswepeer.js';
+
+export interface TileLayout {
+    x : number, y : number,
+    cx : number, cy : number, // центр клетки в единицах стороны
+    shape : TileShape
+};
+
+const SHAPE_CLASSES: Record<TileShape, string> = {
+    [TileShape.Square]: '',
+    [TileShape.Hex]: 'tile-hex',
+    [TileShape.TriangleUp]: 'tile-tri-up',
+    [TileShape.TriangleDown]: 'tile-tri-down',
+};
+
 export class FieldManager {
     private readonly field = $('#game-field');
     
@@ -19,7 +34,12 @@ export class FieldManager {
         tile.removeClass(remove.join(' ')).addClass(add.join(' '));
     }
 
-    public createBoard(width: number, height: number): void {
+    public createBoard(width: number, height: number, layout: TileLayout[] = []): void {
+        if (layout.some(tile => tile.shape !== TileShape.Square)) {
+            this.createShapedBoard(layout);
+            return;
+        }
+        this.field.removeClass('shaped').css({ width: '', height: '' });
         const tileSize = Math.min((Math.min($('#game-frame').height(), $('#game-frame').width()) - 8) / height, 35);
         this.field.empty().append(
             Array.from({ length: height }, (_, y) => 
@@ -34,6 +54,22 @@ export class FieldManager {
         this.field.css('--tile-size', `${tileSize}px`)
     }
 
+    // шестиугольники и треугольники раскладываются абсолютно по центрам из движка
+    private createShapedBoard(layout: TileLayout[]): void {
+        const right = Math.max(...layout.map(tile => tile.cx)) + 1;
+        const bottom = Math.max(...layout.map(tile => tile.cy)) + 1;
+        const frame = Math.min($('#game-frame').height(), $('#game-frame').width()) - 8;
+        const unit = Math.min(frame / Math.max(right, bottom), 35);
+        this.field.empty().addClass('shaped').css({ width: `${right * unit}px`, height: `${bottom * unit}px` }).append(
+            layout.map(tile =>
+                $(`<div class="tile tile-closed ${SHAPE_CLASSES[tile.shape]}" x="${tile.x}" y="${tile.y}"></div>`)
+                    .css({ left: `${tile.cx * unit}px`, top: `${tile.cy * unit}px` })
+                    .on('click', () => this.onTileInteract(tile.x, tile.y))
+            )
+        );
+        this.field.css('--tile-size', `${unit}px`);
+    }
+
     public resetTile(x: number, y: number): void {
         this.updateTileClasses(x, y, ['tile'], ['tile-closed', 'tile-opened', 'tile-flag', 'tile-quant', 'tile-mine', 'right-flag']);
     }
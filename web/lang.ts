@@ -1,4 +1,4 @@
-type Fields = 'quantum_flags' | 'tool_shovel' | 'classic_flag' | 'quant_flag' | 'collapse' | 'new_game' | 'instruction_button' | 'game_settings' | 'label_width' | 'label_height' | 'label_mines' | 'label_uncentainty' | 'start_game' | 'instruction_header' | 'instruction' | 'links_header';
+type Fields = 'quantum_flags' | 'tool_shovel' | 'classic_flag' | 'quant_flag' | 'collapse' | 'new_game' | 'instruction_button' | 'game_settings' | 'label_width' | 'label_height' | 'label_mines' | 'label_uncentainty' | 'label_probes' | 'label_topology' | 'topology_rectangle' | 'topology_cylinder' | 'topology_torus' | 'label_tiling' | 'tiling_square' | 'tiling_hex' | 'tiling_triangle' | 'start_game' | 'instruction_header' | 'instruction' | 'links_header' | 'summary_won' | 'summary_lost' | 'summary_score' | 'summary_time' | 'summary_clicks' | 'summary_flags' | 'summary_quant_flags' | 'summary_collapses' | 'summary_complexity' | 'summary_difficulty' | 'difficulty_easy' | 'difficulty_hard' | 'error_invalid_config' | 'error_unknown';
 type Lang = Record<Fields, string>;
 
 export const RU : Lang = {
@@ -14,10 +14,33 @@ export const RU : Lang = {
     label_height: "Высота (5-30):",
     label_mines: "Квантовые мины (%):",
     label_uncentainty: "Коэффициент запутанности (%):",
+    label_probes: "Щупы (0-20):",
+    label_topology: "Края поля:",
+    topology_rectangle: "Обычные",
+    topology_cylinder: "Склеены слева и справа",
+    topology_torus: "Склеены все (тор)",
+    label_tiling: "Клетки:",
+    tiling_square: "Квадраты",
+    tiling_hex: "Шестиугольники",
+    tiling_triangle: "Треугольники",
     start_game: "Начать игру",
     instruction_header: "Инструкция",
     instruction: "&bull; Все мины находятся в суперпозиции - то есть на нескольких клетках сразу<br>&bull; Вероятность равна 1 / <span class=\"citate\">количество присоединенных клеток</span><br>&bull; Клетка показывает сумму вероятностей вокруг<br>&bull; После коллапса мина оказывается на случайной присоединенной клетке<br>&bull; Для безопасного коллапса используйте квантовые флажки и кнопку \"Сколлапсировать\"<br>&bull; Победа будет, когда все клетки открыты, а мины сколлапсированы и помечены обычными флажками<br>&bull; \"Квантовые мины\" - процент мин от общего числа клеток<br>&bull; \"Коэффициент запутанности\" - процент клеток, которые будут распределены минам от общего числа",
     links_header: "Разработчик",
+    summary_won: "Победа!",
+    summary_lost: "Поражение",
+    summary_score: "Очки: ",
+    summary_time: "Время (с): ",
+    summary_clicks: "Клики (лопатой): ",
+    summary_flags: "Флажки (неверные): ",
+    summary_quant_flags: "Квантовые флажки (поставлено / осталось): ",
+    summary_collapses: "Коллапсы: ",
+    summary_complexity: "Сложность поля (3BV): ",
+    summary_difficulty: "Поле: ",
+    difficulty_easy: "лёгкое",
+    difficulty_hard: "сложное, нужно угадывать",
+    error_invalid_config: "Такие настройки недопустимы: проверьте размеры поля и доли мин",
+    error_unknown: "Не получилось начать игру",
 };
 
 export const EN : Lang = {
@@ -33,10 +56,33 @@ export const EN : Lang = {
     label_height: "Height (5-30):",
     label_mines: "Quantum mines (%):",
     label_uncentainty: "Uncertainty Factor (%):",
+    label_probes: "Probes (0-20):",
+    label_topology: "Board edges:",
+    topology_rectangle: "Regular",
+    topology_cylinder: "Wrap left and right",
+    topology_torus: "Wrap all (torus)",
+    label_tiling: "Tiles:",
+    tiling_square: "Squares",
+    tiling_hex: "Hexagons",
+    tiling_triangle: "Triangles",
     start_game: "Start Game",
     instruction_header: "Instruction",
     instruction: "&bull; All mines in superposition - are at several tiles<br>&bull; Probability is equal 1 / <span class=\"citate\">mine-linked tile count</span><br>&bull; Tiles show sum of probabilities around<br>&bull; After collapse mine go to random linked tile<br>&bull; To safety collapse tile use quantum flag and \"Collapse\" button<br>&bull; ПYou will win, when all tiles are opened and all mines was collapsed and marked classic flags<br>&bull; \"Quantum mines\" - percent of mines from total tiles count<br>&bull; \"Uncertainty Factor\" - percent of tiles that will be distributed to mines from total tiles count",
     links_header: "Delevoper",
+    summary_won: "Victory!",
+    summary_lost: "Defeat",
+    summary_score: "Score: ",
+    summary_time: "Time (s): ",
+    summary_clicks: "Clicks (shovel): ",
+    summary_flags: "Flags (wrong): ",
+    summary_quant_flags: "Quantum flags (used / left): ",
+    summary_collapses: "Collapses: ",
+    summary_complexity: "Board complexity (3BV): ",
+    summary_difficulty: "Board: ",
+    difficulty_easy: "easy",
+    difficulty_hard: "hard, needs guessing",
+    error_invalid_config: "These settings aren't allowed: check the board size and mine shares",
+    error_unknown: "The game can't be started",
 };
 
 async function setStrings(data : Lang): Promise<void> {
@@ -52,10 +98,42 @@ async function setStrings(data : Lang): Promise<void> {
     $('#lheight').text(data.label_height);
     $('#lgroups').text(data.label_mines);
     $('#lcand').text(data.label_uncentainty);
+    $('#lprobes').text(data.label_probes);
+    $('#ltopology').text(data.label_topology);
+    $('#topology-rectangle').text(data.topology_rectangle);
+    $('#topology-cylinder').text(data.topology_cylinder);
+    $('#topology-torus').text(data.topology_torus);
+    $('#ltiling').text(data.label_tiling);
+    $('#tiling-square').text(data.tiling_square);
+    $('#tiling-hex').text(data.tiling_hex);
+    $('#tiling-triangle').text(data.tiling_triangle);
     $('#start-game').text(data.start_game);
     $('#instruction-header').text(data.instruction_header);
     $('#instruction-text').html(data.instruction);
     $('#links-header').text(data.links_header);
+    $('#summary-won').text(data.summary_won);
+    $('#summary-lost').text(data.summary_lost);
+    $('#lsummary-score').text(data.summary_score);
+    $('#lsummary-time').text(data.summary_time);
+    $('#lsummary-clicks').text(data.summary_clicks);
+    $('#lsummary-flags').text(data.summary_flags);
+    $('#lsummary-quant-flags').text(data.summary_quant_flags);
+    $('#lsummary-collapses').text(data.summary_collapses);
+    $('#lsummary-complexity').text(data.summary_complexity);
+    $('#lsummary-difficulty').text(data.summary_difficulty);
+    $('#summary-easy').text(data.difficulty_easy);
+    $('#summary-hard').text(data.difficulty_hard);
+}
+
+// текст ошибки движка по её полю `code`
+const ERRORS: Record<string, Fields> = {
+    invalidConfig: 'error_invalid_config'
+};
+
+let current: Lang = EN;
+
+export function errorText(code: string): string {
+    return current[ERRORS[code] ?? 'error_unknown'];
 }
 
 export const LANGUAGES: Record<string, Lang> = {
@@ -76,6 +154,7 @@ export async function initLanguage(): Promise<void> {
         const baseLanguage = browserLanguage.split('-')[0];
         lang = LANGUAGES[baseLanguage] || EN;
     }
+    current = lang;
     await setStrings(lang);
 
     localStorage.setItem('preferredLanguage', browserLanguage);
@@ -1,8 +1,12 @@
+import { Difficulty } from "../pkg/quantswepeer.js";
+import { GameSummary } from "./types";
+
 export class PopupManager {
     private readonly popups = {
         newGame: $('#popup-new-game'),
         howToPlay: $('#popup-instruction'),
-        links: $('#popup-links')
+        links: $('#popup-links'),
+        summary: $('#popup-summary')
     };
 
     constructor() {
@@ -12,6 +16,7 @@ export class PopupManager {
     private initHandles(): void {
         this.popups.howToPlay.on('click', () => this.closePopup());
         this.popups.links.on('click', () => this.closePopup());
+        this.popups.summary.on('click', () => this.closePopup());
     }
 
     public closePopup(): void {
@@ -29,4 +34,19 @@ export class PopupManager {
     public showLinksPopup(): void {
         this.popups.links.addClass('active');
     }
+
+    public showSummaryPopup(summary: GameSummary): void {
+        $('#summary-won').toggle(summary.won);
+        $('#summary-lost').toggle(!summary.won);
+        $('#summary-score').text(summary.score);
+        $('#summary-time').text((summary.elapsedMs / 1000).toFixed(1));
+        $('#summary-clicks').text(`${summary.clicks} (${summary.opens})`);
+        $('#summary-flags').text(`${summary.flags} (${summary.wrongFlags})`);
+        $('#summary-quant-flags').text(`${summary.quantFlagsUsed} / ${summary.quantFlagsLeft}`);
+        $('#summary-collapses').text(summary.collapses);
+        $('#summary-complexity').text(summary.complexity);
+        $('#summary-easy').toggle(summary.difficulty === Difficulty.Easy);
+        $('#summary-hard').toggle(summary.difficulty === Difficulty.Hard);
+        this.popups.summary.addClass('active');
+    }
 }
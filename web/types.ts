@@ -1,6 +1,40 @@
+// причины изменения клетки (TileChange в движке)
+export const enum ChangeFlag {
+    Status = 1,
+    Sum = 2,       // сумма вероятностей вокруг открытой клетки
+    Collapsed = 4,
+    Mine = 8,
+    Marked = 16    // разметка флажка в конце игры
+};
+
 export interface GameConfig {
     width : number,
     height : number,
     groups : number,
-    candidates : number
-};
\ No newline at end of file
+    candidates : number,
+    topology? : number, // Topology из движка; по умолчанию обычное поле
+    tiling? : number, // Tiling из движка; по умолчанию квадраты
+    minGroupSize? : number, // размеры квантовых групп; по умолчанию 1..4
+    maxGroupSize? : number,
+    groupMines? : number, // мин в каждой группе; по умолчанию 1
+    entangled? : number, // доля групп в запутанных парах; по умолчанию 0
+    probes? : number // щупы (слабые измерения) на игру; по умолчанию 0
+};
+// итог партии из `GameEngine.getSummary`
+export interface GameSummary {
+    finished : boolean,
+    won : boolean,
+    elapsedMs : number,
+    clicks : number,
+    opens : number, // клики лопатой
+    flags : number,
+    wrongFlags : number, // флажки не на минах
+    quantFlagsUsed : number,
+    quantFlagsLeft : number,
+    probesUsed : number,
+    collapses : number,
+    hints : number,
+    complexity : number, // квантовый 3BV поля
+    difficulty : number | null, // Difficulty из движка; null, если поле не сгенерировано или слишком большое для оценки
+    score : number
+};